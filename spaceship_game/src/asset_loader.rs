@@ -1,6 +1,10 @@
+use std::marker::PhantomData;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
 use bevy::prelude::*;
-use bevy::prelude::TimerMode::Repeating;
-use crate::asteroids::SpawnTimer;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::model_pool::{tint_scenes, ModelPool};
 
 #[derive(Resource, Debug, Default)]
 pub struct SceneAssets {
@@ -15,7 +19,9 @@ impl Plugin for AssetLoaderPlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<SceneAssets>()
-            .add_systems(Startup, load_assets);
+            .init_resource::<ModelPool>()
+            .add_systems(Startup, load_assets)
+            .add_systems(Update, tint_scenes);
     }
 }
 
@@ -25,4 +31,38 @@ fn load_assets(mut scene_assets: ResMut<SceneAssets>, asset_server: Res<AssetSer
         missiles: asset_server.load("Missiles.glb#Scene0"),
         spaceship: asset_server.load("Spaceship.glb#Scene0"),
     }
-}
\ No newline at end of file
+}
+
+/// Loads any deserializable asset from a RON file with one of the given extensions.
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    marker: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self { extensions, marker: PhantomData }
+    }
+}
+
+impl<A> AssetLoader for RonAssetLoader<A>
+    where A: Asset + for<'de> Deserialize<'de> {
+    type Asset = A;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(&'a self,
+                reader: &'a mut Reader,
+                _settings: &'a (),
+                _load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<A, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
@@ -1,31 +1,75 @@
-use std::ops::Range;
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
 use bevy::prelude::*;
-use bevy::time::TimerMode::Repeating;
 use rand::Rng;
-use crate::asset_loader::SceneAssets;
-use crate::collision_detection::{Collider, CollisionDamage};
-use crate::health::Health;
+use crate::collision_detection::{Collider, CollisionDamage, CollisionLayers};
+use crate::despawn::despawn_dead_entities;
+use crate::health::{Died, Health};
+use crate::model_pool::{Model, Models};
 use crate::movement::{Acceleration, MovingObjectBundle, Velocity};
+use crate::play_area::Wraps;
+use crate::rng::GameRng;
+use crate::score::ScoreValue;
 use crate::schedule::InGameSet;
-
-const VELOCITY_SCALAR: f32 = 5.0;
-const ACCELERATION_SCALAR: f32 = 1.0;
-const SPAWN_RANGE_X: Range<f32> = -25.0..25.0;
-const SPAWN_RANGE_Z: Range<f32> = 0.0..25.0;
-const SPAWN_TIME_SECONDS: f32 = 1.0;
+use crate::waves::Hostile;
 
 const ROTATE_SPEED: f32 = 2.0;
-const RADIUS: f32 = 2.5;
+const FRAGMENT_COUNT: RangeInclusive<u32> = 2..=3;
 
-const HEALTH: f32 = 35.0;
 const COLLISION_DAMAGE: f32 = 35.0;
 
+pub const ASTEROID_LAYERS: CollisionLayers = CollisionLayers::new(CollisionLayers::ASTEROID, !CollisionLayers::ASTEROID);
+
+const LARGE: SizeTier = SizeTier { radius: 2.5, health: 35.0, speed: 5.0, scale: 1.0, points: 20 };
+const MEDIUM: SizeTier = SizeTier { radius: 1.5, health: 20.0, speed: 3.0, scale: 0.6, points: 50 };
+const SMALL: SizeTier = SizeTier { radius: 0.8, health: 10.0, speed: 4.0, scale: 0.32, points: 100 };
+
 #[derive(Component, Debug)]
 pub struct Asteroid;
 
-#[derive(Resource, Debug)]
-pub struct SpawnTimer {
-    timer: Timer,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeTier {
+    pub radius: f32,
+    pub health: f32,
+    /// Speed fragments of this size scatter with, on top of the velocity of the asteroid they split from.
+    pub speed: f32,
+    pub scale: f32,
+    /// Score for destroying an asteroid of this size, smaller ones are harder to hit.
+    pub points: u32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsteroidSize {
+    Large,
+    Medium,
+    Small,
+}
+
+impl AsteroidSize {
+    pub fn tier(&self) -> SizeTier {
+        match self {
+            AsteroidSize::Large => LARGE,
+            AsteroidSize::Medium => MEDIUM,
+            AsteroidSize::Small => SMALL,
+        }
+    }
+
+    /// Size of the fragments this asteroid splits into, `None` if it doesn't split.
+    pub fn fragment_size(&self) -> Option<AsteroidSize> {
+        match self {
+            AsteroidSize::Large => Some(AsteroidSize::Medium),
+            AsteroidSize::Medium => Some(AsteroidSize::Small),
+            AsteroidSize::Small => None,
+        }
+    }
+}
+
+/// Sent when an asteroid's health runs out, before it is despawned.
+#[derive(Event, Debug)]
+pub struct AsteroidDestroyed {
+    pub translation: Vec3,
+    pub velocity: Vec3,
+    pub size: AsteroidSize,
 }
 
 pub struct AsteroidPlugin;
@@ -33,49 +77,175 @@ pub struct AsteroidPlugin;
 impl Plugin for AsteroidPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(SpawnTimer { timer: Timer::from_seconds(SPAWN_TIME_SECONDS, Repeating) })
+            .add_event::<AsteroidDestroyed>()
             .add_systems(
-                Update,
-                (spawn_asteroids, rotate_asteroids)
-                    .in_set(InGameSet::EntityUpdates),
-            );
+                FixedUpdate,
+                detect_destroyed_asteroids
+                    .after(despawn_dead_entities)
+                    .in_set(InGameSet::DespawnEntities),
+            )
+            .add_systems(FixedUpdate, (rotate_asteroids, split_asteroids).in_set(InGameSet::EntityUpdates));
     }
 }
 
-fn spawn_asteroids(mut commands: Commands,
-                   mut spawn_timer: ResMut<SpawnTimer>,
-                   time: Res<Time>,
-                   scene_assets: Res<SceneAssets>) {
-    spawn_timer.timer.tick(time.delta());
-    if !spawn_timer.timer.just_finished() {
-        return;
-    }
-    let mut rand = rand::thread_rng();
-    let translation = Vec3::new(rand.gen_range(SPAWN_RANGE_X), 0.0, rand.gen_range(SPAWN_RANGE_Z));
-    let mut random_unit_vector = || Vec3::new(rand.gen_range(-1.0..1.0), 0., rand.gen_range(-1.0..1.0)).normalize_or_zero();
-    let velocity = random_unit_vector() * VELOCITY_SCALAR;
-    let acceleration = random_unit_vector() * ACCELERATION_SCALAR;
-
-    commands.spawn((
+pub fn spawn_asteroid(commands: &mut Commands,
+                      models: &mut Models,
+                      size: AsteroidSize,
+                      translation: Vec3,
+                      velocity: Vec3,
+                      acceleration: Vec3,
+                      health: f32) {
+    let tier = size.tier();
+    models.spawn(commands, Model::Asteroid, (
         MovingObjectBundle {
             velocity: Velocity::new(velocity),
             acceleration: Acceleration::new(acceleration),
-            collider: Collider::new(RADIUS),
-            model: SceneBundle {
-                scene: scene_assets.asteroid.clone(),
-                transform: Transform::from_translation(translation),
-                ..default()
-            },
+            collider: Collider::new(tier.radius),
+            spatial: SpatialBundle::from_transform(
+                Transform::from_translation(translation).with_scale(Vec3::splat(tier.scale)),
+            ),
         },
         Asteroid,
-        Health::new(HEALTH),
+        Hostile,
+        size,
+        Wraps,
+        ScoreValue(tier.points),
+        Health::new(health),
         CollisionDamage::new(COLLISION_DAMAGE),
+        ASTEROID_LAYERS,
     ));
 }
 
-fn rotate_asteroids(mut query: Query<(&mut Transform), With<Asteroid>>,
+fn rotate_asteroids(mut query: Query<&mut Transform, With<Asteroid>>,
                     time: Res<Time>) {
     for mut transform in query.iter_mut() {
         transform.rotate_local_z(ROTATE_SPEED * time.delta_seconds())
     }
-}
\ No newline at end of file
+}
+
+// dead asteroids are still around here, their despawn commands haven't been applied yet
+fn detect_destroyed_asteroids(mut event_writer: EventWriter<AsteroidDestroyed>,
+                              mut died_reader: EventReader<Died>,
+                              query: Query<(&Transform, &Velocity, &AsteroidSize), With<Asteroid>>) {
+    for died in died_reader.read() {
+        let Ok((transform, velocity, &size)) = query.get(died.entity) else {
+            continue;
+        };
+        event_writer.send(AsteroidDestroyed {
+            translation: transform.translation,
+            velocity: velocity.value,
+            size,
+        });
+    }
+}
+
+/// Fragments scatter evenly around the destroyed asteroid and all inherit its velocity,
+/// so together they keep moving the way it did.
+pub fn split_asteroids(mut commands: Commands,
+                       mut event_reader: EventReader<AsteroidDestroyed>,
+                       mut rng: ResMut<GameRng>,
+                       mut models: Models) {
+    for destroyed in event_reader.read() {
+        let Some(fragment_size) = destroyed.size.fragment_size() else {
+            continue;
+        };
+        let tier = fragment_size.tier();
+        let count = rng.gen_range(FRAGMENT_COUNT);
+        let start_angle = rng.gen_range(0.0..TAU);
+
+        for i in 0..count {
+            let angle = start_angle + TAU * i as f32 / count as f32;
+            let direction = Vec3::new(angle.cos(), 0.0, angle.sin());
+            spawn_asteroid(
+                &mut commands,
+                &mut models,
+                fragment_size,
+                destroyed.translation + direction * tier.radius,
+                destroyed.velocity + direction * tier.speed,
+                Vec3::ZERO,
+                tier.health,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::despawn::DespawnPlugin;
+    use crate::health::HealthPlugin;
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    fn asteroid_app(seed: u64) -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins((AsteroidPlugin, HealthPlugin, DespawnPlugin))
+            .insert_resource(GameRng::from_seed(seed));
+        start_game(&mut app);
+        app
+    }
+
+    fn spawn(app: &mut App, size: AsteroidSize, translation: Vec3, velocity: Vec3) {
+        app.world.run_system_once(move |mut commands: Commands, mut models: Models| {
+            spawn_asteroid(&mut commands, &mut models, size, translation, velocity, Vec3::ZERO, size.tier().health);
+        });
+    }
+
+    fn destroy_asteroids(app: &mut App) {
+        for mut health in app.world.query_filtered::<&mut Health, With<Asteroid>>().iter_mut(&mut app.world) {
+            health.current = 0.0;
+        }
+        run_ticks(app, 1);
+    }
+
+    fn asteroids(app: &mut App) -> Vec<(Vec3, Vec3, AsteroidSize)> {
+        app.world
+            .query_filtered::<(&Transform, &Velocity, &AsteroidSize), With<Asteroid>>()
+            .iter(&app.world)
+            .map(|(transform, velocity, &size)| (transform.translation, velocity.value, size))
+            .collect()
+    }
+
+    #[test]
+    fn large_asteroids_split_into_medium_fragments_that_keep_their_momentum() {
+        let translation = Vec3::new(10.0, 0.0, 0.0);
+        let velocity = Vec3::new(3.0, 0.0, 0.0);
+        for seed in 0..8 {
+            let mut app = asteroid_app(seed);
+            spawn(&mut app, AsteroidSize::Large, translation, velocity);
+            destroy_asteroids(&mut app);
+
+            let fragments = asteroids(&mut app);
+            assert!(FRAGMENT_COUNT.contains(&(fragments.len() as u32)), "{} fragments", fragments.len());
+            let mut total_velocity = Vec3::ZERO;
+            for (fragment_translation, fragment_velocity, size) in fragments.iter().copied() {
+                assert_eq!(size, AsteroidSize::Medium);
+                assert!((fragment_translation.distance(translation) - MEDIUM.radius).abs() < 1e-4);
+                assert!(((fragment_velocity - velocity).length() - MEDIUM.speed).abs() < 1e-4);
+                total_velocity += fragment_velocity;
+            }
+            // the scatter cancels out, so together the fragments move like the asteroid did
+            assert!((total_velocity / fragments.len() as f32).distance(velocity) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn medium_asteroids_split_into_small_ones() {
+        let mut app = asteroid_app(0);
+        spawn(&mut app, AsteroidSize::Medium, Vec3::ZERO, Vec3::ZERO);
+        destroy_asteroids(&mut app);
+        let fragments = asteroids(&mut app);
+        assert!(FRAGMENT_COUNT.contains(&(fragments.len() as u32)));
+        assert!(fragments.iter().all(|&(_, _, size)| size == AsteroidSize::Small));
+    }
+
+    #[test]
+    fn small_asteroids_do_not_split() {
+        let mut app = asteroid_app(0);
+        spawn(&mut app, AsteroidSize::Small, Vec3::ZERO, Vec3::ZERO);
+        destroy_asteroids(&mut app);
+        run_ticks(&mut app, 1);
+        assert!(asteroids(&mut app).is_empty());
+    }
+}
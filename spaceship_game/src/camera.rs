@@ -1,18 +1,294 @@
 use bevy::prelude::*;
+use crate::health::{DamageTaken, Died, Health};
+use crate::movement::{interpolate_transforms, Velocity};
+use crate::play_area::{PlayArea, PlayAreaShape};
+use crate::run_session::{ResetRun, RunSessionSet};
+use crate::spaceship::Spaceship;
 
-const CAMERA_DISTANCE: f32 = 80.0;
+pub const CAMERA_DISTANCE: f32 = 80.0;
+const MAX_ZOOM_OUT: f32 = 30.0;
+const MAX_SHAKE_OFFSET: f32 = 2.5;
+/// How fast the shake wobbles, in radians per second.
+const SHAKE_FREQUENCY: f32 = 40.0;
+const EXPLOSION_TRAUMA: f32 = 0.15;
+
+/// Follows the spaceship from above, zooming out with its speed and shaking on hits and explosions.
+#[derive(Component, Debug)]
+pub struct CameraRig {
+    /// Distance the spaceship can move from the camera's focus before the camera follows.
+    pub deadzone: f32,
+    /// How quickly the focus and zoom catch up, higher is snappier.
+    pub damping: f32,
+    pub height: f32,
+    /// Extra height per unit of spaceship speed.
+    pub zoom_per_speed: f32,
+    /// Trauma lost per second, the shake is proportional to trauma squared.
+    pub trauma_decay: f32,
+    /// Trauma added per point of damage taken, relative to max health.
+    pub damage_trauma: f32,
+    focus: Vec3,
+    zoom: f32,
+    trauma: f32,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            deadzone: 10.0,
+            damping: 4.0,
+            height: CAMERA_DISTANCE,
+            zoom_per_speed: 0.8,
+            trauma_decay: 1.5,
+            damage_trauma: 1.5,
+            focus: Vec3::ZERO,
+            zoom: 0.0,
+            trauma: 0.0,
+        }
+    }
+}
+
+impl CameraRig {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+}
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera);
+        app
+            .add_systems(Startup, spawn_camera)
+            // follows where the spaceship is drawn, not where the last tick left it
+            .add_systems(Update, (add_camera_trauma, update_camera_rig).chain().after(interpolate_transforms))
+            .add_systems(Update, fit_play_area_to_view)
+            .add_systems(ResetRun, reset_camera_rig.in_set(RunSessionSet::Reset))
+        ;
     }
 }
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn(Camera3dBundle{
-        transform: Transform::from_xyz(0., CAMERA_DISTANCE, 0.).looking_at(Vec3::ZERO, Vec3::Z),
-        ..default()
-    });
-}
\ No newline at end of file
+    commands.spawn((
+        Camera3dBundle{
+            transform: Transform::from_xyz(0., CAMERA_DISTANCE, 0.).looking_at(Vec3::ZERO, Vec3::Z),
+            ..default()
+        },
+        CameraRig::default(),
+    ));
+}
+
+/// Half the width and depth of what a camera looking straight down from `height` sees of the XZ plane.
+pub fn visible_half_extents(projection: &PerspectiveProjection, height: f32) -> Vec2 {
+    let half_depth = height * (projection.fov / 2.0).tan();
+    Vec2::new(half_depth * projection.aspect_ratio, half_depth)
+}
+
+/// Sizes a wrapping play area to the view, whenever the window and with it the projection changes.
+fn fit_play_area_to_view(mut play_area: ResMut<PlayArea>,
+                         camera_query: Query<(&Projection, &CameraRig), Changed<Projection>>) {
+    if !play_area.wraps() {
+        return;
+    }
+    let Ok((Projection::Perspective(projection), rig)) = camera_query.get_single() else {
+        return;
+    };
+    play_area.shape = PlayAreaShape::Rectangle { half_extents: visible_half_extents(projection, rig.height) };
+}
+
+/// Moves `focus` towards `target` until `target` is back inside the deadzone around it.
+/// `damping` is frame rate independent: the remaining distance shrinks by `exp(-damping)` every second.
+pub fn follow(focus: Vec3, target: Vec3, deadzone: f32, damping: f32, delta_seconds: f32) -> Vec3 {
+    let offset = target - focus;
+    let distance = offset.length();
+    if distance <= deadzone {
+        return focus;
+    }
+    let desired = target - offset / distance * deadzone;
+    focus.lerp(desired, smoothing(damping, delta_seconds))
+}
+
+/// Share of the remaining distance to cover this frame.
+fn smoothing(damping: f32, delta_seconds: f32) -> f32 {
+    1.0 - (-damping * delta_seconds).exp()
+}
+
+fn reset_camera_rig(mut rig_query: Query<&mut CameraRig>) {
+    for mut rig in rig_query.iter_mut() {
+        let default = CameraRig::default();
+        rig.focus = default.focus;
+        rig.zoom = default.zoom;
+        rig.trauma = default.trauma;
+    }
+}
+
+fn add_camera_trauma(mut rig_query: Query<&mut CameraRig>,
+                     mut damage_reader: EventReader<DamageTaken>,
+                     mut died_reader: EventReader<Died>,
+                     spaceship_query: Query<&Health, With<Spaceship>>) {
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+    for damage in damage_reader.read() {
+        if let Ok(health) = spaceship_query.get(damage.entity) {
+            let trauma = rig.damage_trauma * damage.amount / health.max;
+            rig.add_trauma(trauma);
+        }
+    }
+    // projectiles die on every hit, only count what's worth an explosion
+    let explosions = died_reader.read().filter(|died| died.killer.is_some()).count();
+    rig.add_trauma(EXPLOSION_TRAUMA * explosions as f32);
+}
+
+fn update_camera_rig(mut rig_query: Query<(&mut Transform, &mut CameraRig), Without<Spaceship>>,
+                     spaceship_query: Query<(&Transform, &Velocity), With<Spaceship>>,
+                     play_area: Res<PlayArea>,
+                     time: Res<Time>) {
+    let Ok((mut transform, mut rig)) = rig_query.get_single_mut() else {
+        return;
+    };
+    let delta_seconds = time.delta_seconds();
+
+    // a wrapping play area is sized to the view, so the camera stays put
+    if let Ok((spaceship_transform, velocity)) = spaceship_query.get_single() {
+        if !play_area.wraps() {
+            let target = spaceship_transform.translation * Vec3::new(1.0, 0.0, 1.0);
+            rig.focus = follow(rig.focus, target, rig.deadzone, rig.damping, delta_seconds);
+            let zoom = (velocity.value.length() * rig.zoom_per_speed).min(MAX_ZOOM_OUT);
+            rig.zoom += (zoom - rig.zoom) * smoothing(rig.damping, delta_seconds);
+        }
+    }
+
+    rig.trauma = (rig.trauma - rig.trauma_decay * delta_seconds).max(0.0);
+    let time = time.elapsed_seconds() * SHAKE_FREQUENCY;
+    let shake = Vec3::new(time.sin(), 0.0, (time * 1.3 + 1.7).sin()) * rig.trauma.powi(2) * MAX_SHAKE_OFFSET;
+
+    transform.translation = rig.focus + Vec3::Y * (rig.height + rig.zoom) + shake;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::headless_app;
+    use super::*;
+
+    const DEADZONE: f32 = 10.0;
+    const DAMPING: f32 = 4.0;
+
+    fn follow_for(seconds: f32, frames: usize, target: Vec3) -> Vec3 {
+        let mut focus = Vec3::ZERO;
+        for _ in 0..frames {
+            focus = follow(focus, target, DEADZONE, DAMPING, seconds / frames as f32);
+        }
+        focus
+    }
+
+    #[test]
+    fn focus_stays_put_while_the_target_is_inside_the_deadzone() {
+        let target = Vec3::new(6.0, 0.0, -8.0);
+        assert_eq!(follow(Vec3::ZERO, target, DEADZONE, DAMPING, 1.0), Vec3::ZERO);
+    }
+
+    #[test]
+    fn focus_closes_in_until_the_target_is_at_the_deadzone_edge() {
+        let target = Vec3::new(30.0, 0.0, 0.0);
+        let one_step = follow(Vec3::ZERO, target, DEADZONE, DAMPING, 0.1);
+        assert!(one_step.x > 0.0 && one_step.x < 20.0);
+        assert_eq!(one_step.z, 0.0);
+
+        // the distance left to the deadzone edge shrinks by exp(-damping) every second
+        let after_one_second = follow_for(1.0, 60, target);
+        assert!((20.0 - after_one_second.x - 20.0 * (-DAMPING).exp()).abs() < 1e-3);
+
+        let settled = follow_for(10.0, 600, target);
+        assert!((settled.distance(target) - DEADZONE).abs() < 1e-3);
+    }
+
+    #[test]
+    fn following_does_not_depend_on_the_frame_rate() {
+        let target = Vec3::new(-25.0, 0.0, 40.0);
+        let at_30_fps = follow_for(0.5, 15, target);
+        let at_144_fps = follow_for(0.5, 72, target);
+        assert!(at_30_fps.distance(at_144_fps) < 1e-3);
+    }
+
+    fn camera_app(play_area: PlayArea) -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins(CameraPlugin)
+            .add_event::<DamageTaken>()
+            .add_event::<Died>()
+            .insert_resource(play_area)
+        ;
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(40.0, 0.0, 0.0)),
+            Velocity::new(Vec3::new(20.0, 0.0, 0.0)),
+            Spaceship,
+        ));
+        app.update();
+        app
+    }
+
+    fn rig(app: &mut App) -> (Vec3, f32, f32) {
+        let rig = app.world.query::<&CameraRig>().single(&app.world);
+        (rig.focus, rig.zoom, rig.trauma)
+    }
+
+    #[test]
+    fn the_rig_follows_and_zooms_out_with_speed() {
+        let mut app = camera_app(PlayArea::default());
+        for _ in 0..60 {
+            app.update();
+        }
+        let (focus, zoom, _) = rig(&mut app);
+        assert!(focus.x > 0.0);
+        assert!(zoom > 0.0);
+    }
+
+    #[test]
+    fn the_rig_stays_put_in_a_wrapping_play_area() {
+        let mut app = camera_app(PlayArea::wrapping());
+        for _ in 0..60 {
+            app.update();
+        }
+        let (focus, zoom, _) = rig(&mut app);
+        assert_eq!(focus, Vec3::ZERO);
+        assert_eq!(zoom, 0.0);
+    }
+
+    #[test]
+    fn a_wrapping_play_area_fits_the_view() {
+        let mut app = camera_app(PlayArea::wrapping());
+        let half_extents = |app: &App| match app.world.resource::<PlayArea>().shape {
+            PlayAreaShape::Rectangle { half_extents } => half_extents,
+            shape => panic!("{:?}", shape),
+        };
+        // a 16:9 window shows about 59 by 33 units of the plane
+        let mut projection = app.world.query::<&mut Projection>().single_mut(&mut app.world);
+        *projection = Projection::Perspective(PerspectiveProjection { aspect_ratio: 16.0 / 9.0, ..default() });
+        app.update();
+        assert!(half_extents(&app).abs_diff_eq(Vec2::new(58.9, 33.1), 0.1), "{:?}", half_extents(&app));
+        assert_eq!(*app.world.resource::<PlayArea>(), PlayArea::wrapping());
+
+        let mut projection = app.world.query::<&mut Projection>().single_mut(&mut app.world);
+        *projection = Projection::Perspective(PerspectiveProjection { aspect_ratio: 4.0 / 3.0, ..default() });
+        app.update();
+        assert!(half_extents(&app).abs_diff_eq(Vec2::new(44.2, 33.1), 0.1), "{:?}", half_extents(&app));
+    }
+
+    #[test]
+    fn a_despawning_play_area_ignores_the_view() {
+        let mut app = camera_app(PlayArea::default());
+        app.update();
+        assert_eq!(*app.world.resource::<PlayArea>(), PlayArea::default());
+    }
+
+    #[test]
+    fn a_restart_resets_the_rig() {
+        let mut app = camera_app(PlayArea::default());
+        for _ in 0..60 {
+            app.update();
+        }
+        app.world.query::<&mut CameraRig>().single_mut(&mut app.world).add_trauma(1.0);
+        app.world.run_schedule(ResetRun);
+        assert_eq!(rig(&mut app), (Vec3::ZERO, 0.0, 0.0));
+    }
+}
@@ -1,9 +1,13 @@
+use std::mem;
+use std::time::Duration;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
-use crate::asteroids::Asteroid;
-use crate::health::Health;
+use bevy::utils::HashSet;
+use crate::health::{DamageTaken, Health};
+use crate::play_area::{PlayArea, Wraps};
+use crate::run_session::{ResetRun, RunSessionSet};
 use crate::schedule::InGameSet;
-use crate::spaceship::{Spaceship, SpaceshipMissile};
+use crate::shield::SpaceshipShield;
+use crate::spatial_hash::SpatialHashGrid;
 
 #[derive(Component, Debug)]
 pub struct Collider {
@@ -17,6 +21,46 @@ impl Collider {
     }
 }
 
+/// Opts a collider into swept collision tests between its previous and current position,
+/// so fast objects can't tunnel through others on a long frame.
+#[derive(Component, Debug, Default)]
+pub struct ContinuousCollision {
+    pub previous_translation: Option<Vec3>,
+}
+
+/// Two colliders interact only if each one's `filter` contains a layer of the other's `membership`.
+/// Colliders without this component are members of every layer and interact with everything.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionLayers {
+    pub membership: u32,
+    pub filter: u32,
+}
+
+impl CollisionLayers {
+    pub const NONE: u32 = 0;
+    pub const ALL: u32 = u32::MAX;
+    pub const SPACESHIP: u32 = 1 << 0;
+    pub const SPACESHIP_MISSILE: u32 = 1 << 1;
+    pub const ASTEROID: u32 = 1 << 2;
+    pub const ENEMY: u32 = 1 << 3;
+    pub const ENEMY_PROJECTILE: u32 = 1 << 4;
+    pub const PICKUP: u32 = 1 << 5;
+
+    pub const fn new(membership: u32, filter: u32) -> Self {
+        Self { membership, filter }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.filter & other.membership != Self::NONE && other.filter & self.membership != Self::NONE
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::new(Self::ALL, Self::ALL)
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct CollisionDamage {
     pub amount: f32,
@@ -28,6 +72,27 @@ impl CollisionDamage {
     }
 }
 
+/// Entity credited with the damage this entity deals, e.g. the ship that fired a missile.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Owner(pub Entity);
+
+/// Invulnerability frames after taking collision damage.
+/// Entities with a cooldown keep taking damage from a lasting contact every time it runs out,
+/// entities without one are damaged only once per contact.
+#[derive(Component, Debug)]
+pub struct DamageCooldown {
+    pub timer: Timer,
+}
+
+impl DamageCooldown {
+    pub fn new(seconds: f32) -> Self {
+        let mut timer = Timer::from_seconds(seconds, TimerMode::Once);
+        timer.tick(Duration::from_secs_f32(seconds));
+        Self { timer }
+    }
+}
+
+/// Sent every frame while two colliders overlap.
 #[derive(Event, Debug)]
 pub struct CollisionEvent {
     pub entity: Entity,
@@ -40,83 +105,439 @@ impl CollisionEvent {
     }
 }
 
+/// Sent on the first frame two colliders overlap.
+#[derive(Event, Debug)]
+pub struct CollisionStarted {
+    pub entity: Entity,
+    pub collided_entity: Entity,
+}
+
+impl CollisionStarted {
+    pub fn new(entity: Entity, collided_entity: Entity) -> Self {
+        Self { entity, collided_entity }
+    }
+}
+
+/// Sent on the first frame two colliders stop overlapping, including when one of them was despawned.
+#[derive(Event, Debug)]
+pub struct CollisionEnded {
+    pub entity: Entity,
+    pub collided_entity: Entity,
+}
+
+impl CollisionEnded {
+    pub fn new(entity: Entity, collided_entity: Entity) -> Self {
+        Self { entity, collided_entity }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct CollisionContacts {
+    current: HashSet<(Entity, Entity)>,
+    previous: HashSet<(Entity, Entity)>,
+}
+
 pub struct CollisionDetectionPlugin;
 
 impl Plugin for CollisionDetectionPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, collision_detection.in_set(InGameSet::CollisionDetection))
-            .add_systems(Update, (
-                (
-                    handle_collisions::<Asteroid>,
-                    handle_collisions::<Spaceship>,
-                    handle_collisions::<SpaceshipMissile>,
-                ),
+            .init_resource::<SpatialHashGrid>()
+            .init_resource::<CollisionContacts>()
+            .add_systems(FixedUpdate, collision_detection.in_set(InGameSet::CollisionDetection))
+            .add_systems(FixedUpdate, (
+                tick_damage_cooldowns,
+                handle_collisions,
                 apply_collision_damage,
             )
                 .chain()
                 .in_set(InGameSet::EntityUpdates),
             )
+            .add_systems(ResetRun, reset_collision_contacts.in_set(RunSessionSet::Reset))
             .add_event::<CollisionEvent>()
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionEnded>()
         ;
     }
 }
 
-fn collision_detection(mut query: Query<(Entity, &GlobalTransform, &mut Collider)>) {
-    let mut colliding_entities: HashMap<Entity, Vec<Entity>> = HashMap::new();
-
-    for (entity_a, transform_a, collider_a) in query.iter() {
-        for (entity_b, transform_b, collider_b) in query.iter() {
-            if entity_a == entity_b {
-                continue;
+// Colliders are top-level entities, so `Transform` is used directly:
+// `GlobalTransform` only gets propagated once per frame, not after every fixed tick.
+fn collision_detection(mut grid: ResMut<SpatialHashGrid>,
+                       play_area: Res<PlayArea>,
+                       mut query: Query<(Entity, &Transform, &mut Collider, Option<&CollisionLayers>)>,
+                       mut continuous_query: Query<(&Transform, &mut ContinuousCollision)>,
+                       wraps_query: Query<(), With<Wraps>>) {
+    grid.clear();
+    for (entity, transform, collider, layers) in query.iter() {
+        let layers = layers.copied().unwrap_or_default();
+        let translation = transform.translation;
+        let id = match continuous_query.get(entity) {
+            Ok((_, ContinuousCollision { previous_translation: Some(previous) })) => {
+                grid.insert_swept(entity, *previous, translation, collider.radius, layers)
             }
-            let distance = transform_a.translation().distance(transform_b.translation());
-            if distance < collider_a.radius + collider_b.radius {
-                colliding_entities
-                    .entry(entity_a)
-                    .or_insert_with(Vec::new)
-                    .push(entity_b);
+            _ => grid.insert(entity, translation, collider.radius, layers),
+        };
+        // a wrapping entity that sticks out over an edge also collides with what's at the opposite edge
+        if play_area.wraps() && wraps_query.contains(entity) {
+            for image in play_area.images(translation, collider.radius) {
+                grid.insert_image(id, image, collider.radius, layers);
             }
         }
     }
+    grid.find_pairs();
+
+    for (transform, mut continuous) in continuous_query.iter_mut() {
+        continuous.previous_translation = Some(transform.translation);
+    }
 
-    for (entity, _, mut collider) in query.iter_mut() {
+    for (_, _, mut collider, _) in query.iter_mut() {
         collider.colliding_entities.clear();
-        if let Some(collisions) = colliding_entities.get(&entity) {
-            collider
-                .colliding_entities
-                .extend(collisions.iter().copied());
+    }
+    for (entity_a, entity_b) in grid.pairs() {
+        if let Ok((_, _, mut collider_a, _)) = query.get_mut(entity_a) {
+            collider_a.colliding_entities.push(entity_b);
+        }
+        if let Ok((_, _, mut collider_b, _)) = query.get_mut(entity_b) {
+            collider_b.colliding_entities.push(entity_a);
+        }
+    }
+}
+
+/// Ends every contact of `entity` right away, for when it's pooled and handed out again as something else.
+/// Colliders can't tell it apart from what it was otherwise, and a contact with it would seem to continue.
+pub fn end_contacts(world: &mut World, entity: Entity) {
+    let Some(mut collider) = world.get_mut::<Collider>(entity) else {
+        return;
+    };
+    // contacts found last tick are only handled this tick, so they're still listed on the colliders
+    for colliding in mem::take(&mut collider.colliding_entities) {
+        if let Some(mut other) = world.get_mut::<Collider>(colliding) {
+            other.colliding_entities.retain(|&other_colliding| other_colliding != entity);
+        }
+    }
+    let Some(mut contacts) = world.get_resource_mut::<CollisionContacts>() else {
+        return;
+    };
+    let mut ended = vec![];
+    contacts.current.retain(|&(a, b)| {
+        let involved = a == entity || b == entity;
+        if involved {
+            ended.push(CollisionEnded::new(a, b));
         }
+        !involved
+    });
+    world.send_event_batch(ended);
+}
+
+// contacts with entities of the previous run would otherwise be reported as ended
+fn reset_collision_contacts(mut contacts: ResMut<CollisionContacts>) {
+    contacts.current.clear();
+    contacts.previous.clear();
+}
+
+fn tick_damage_cooldowns(mut query: Query<&mut DamageCooldown>, time: Res<Time>) {
+    for mut cooldown in query.iter_mut() {
+        cooldown.timer.tick(time.delta());
     }
 }
 
-fn handle_collisions<T: Component>(mut event_writer: EventWriter<CollisionEvent>,
-                                   query: Query<(Entity, &Collider), With<T>>) {
+fn handle_collisions(mut contacts: ResMut<CollisionContacts>,
+                     mut collision_writer: EventWriter<CollisionEvent>,
+                     mut started_writer: EventWriter<CollisionStarted>,
+                     mut ended_writer: EventWriter<CollisionEnded>,
+                     query: Query<(Entity, &Collider)>) {
+    let contacts = &mut *contacts;
+    mem::swap(&mut contacts.current, &mut contacts.previous);
+    contacts.current.clear();
+
     for (entity, collider) in query.iter() {
         for &collider_entity in collider.colliding_entities.iter() {
-            // Entity collided with the entity of the same type
-            if query.get(collider_entity).is_ok() {
-                continue;
+            contacts.current.insert((entity, collider_entity));
+            if !contacts.previous.contains(&(entity, collider_entity)) {
+                started_writer.send(CollisionStarted::new(entity, collider_entity));
             }
-            event_writer.send(CollisionEvent::new(entity, collider_entity));
+            collision_writer.send(CollisionEvent::new(entity, collider_entity));
         }
     }
+
+    for &(entity, collided_entity) in contacts.previous.difference(&contacts.current) {
+        ended_writer.send(CollisionEnded::new(entity, collided_entity));
+    }
 }
 
-pub fn apply_collision_damage(mut event_reader: EventReader<CollisionEvent>,
-                              mut health_query: Query<(&mut Health)>,
-                              mut collision_damage_query: Query<(&CollisionDamage)>,
+pub fn apply_collision_damage(mut started_reader: EventReader<CollisionStarted>,
+                              mut collision_reader: EventReader<CollisionEvent>,
+                              mut damage_writer: EventWriter<DamageTaken>,
+                              mut health_query: Query<(&mut Health, Option<&mut DamageCooldown>, Has<SpaceshipShield>)>,
+                              collision_damage_query: Query<(&CollisionDamage, Option<&Owner>)>,
 ) {
-    for &CollisionEvent {
-        entity, collided_entity
-    } in event_reader.read() {
-        let Ok(mut health) = health_query.get_mut(entity) else {
+    let started = started_reader
+        .read()
+        .map(|event| (event.entity, event.collided_entity, true));
+    let lasting = collision_reader
+        .read()
+        .map(|event| (event.entity, event.collided_entity, false));
+
+    for (entity, collided_entity, just_started) in started.chain(lasting) {
+        let Ok((mut health, mut cooldown, shielded)) = health_query.get_mut(entity) else {
             continue;
         };
-
-        let Ok(collision_damage) = collision_damage_query.get(collided_entity) else {
+        let Ok((collision_damage, owner)) = collision_damage_query.get(collided_entity) else {
             continue;
         };
-        health.value -= collision_damage.amount;
+
+        match &cooldown {
+            Some(cooldown) if !cooldown.timer.finished() => continue,
+            None if !just_started => continue,
+            _ => (),
+        }
+        let absorption = if shielded { SpaceshipShield::ABSORPTION } else { 0.0 };
+        let source = owner.map_or(collided_entity, |owner| owner.0);
+        let amount = health.take_damage(collision_damage.amount * (1.0 - absorption), source);
+        if amount > 0.0 {
+            if let Some(cooldown) = &mut cooldown {
+                cooldown.timer.reset();
+            }
+            damage_writer.send(DamageTaken { entity, amount });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asteroids::ASTEROID_LAYERS;
+    use crate::enemies::{UFO_LAYERS, UFO_PROJECTILE_LAYERS};
+    use crate::pickups::PICKUP_LAYERS;
+    use crate::health::HealthPlugin;
+    use crate::movement::{MovementPlugin, Velocity};
+    use crate::play_area::{PlayAreaPlugin, PlayAreaShape};
+    use crate::spaceship::{SPACESHIP_LAYERS, SPACESHIP_MISSILE_LAYERS};
+    use crate::testing::{headless_app, headless_app_with_tick_rate, run_ticks, start_game, EventCollector};
+    use super::*;
+
+    fn assert_interacts(a: CollisionLayers, b: CollisionLayers, expected: bool) {
+        assert_eq!(a.interacts_with(&b), expected, "{:?} with {:?}", a, b);
+        assert_eq!(b.interacts_with(&a), expected, "{:?} with {:?}", b, a);
+    }
+
+    #[test]
+    fn asteroids_hit_the_spaceship_and_its_missiles() {
+        assert_interacts(ASTEROID_LAYERS, SPACESHIP_LAYERS, true);
+        assert_interacts(ASTEROID_LAYERS, SPACESHIP_MISSILE_LAYERS, true);
+    }
+
+    #[test]
+    fn missiles_ignore_the_spaceship() {
+        assert_interacts(SPACESHIP_MISSILE_LAYERS, SPACESHIP_LAYERS, false);
+    }
+
+    #[test]
+    fn same_types_ignore_each_other() {
+        assert_interacts(ASTEROID_LAYERS, ASTEROID_LAYERS, false);
+        assert_interacts(SPACESHIP_MISSILE_LAYERS, SPACESHIP_MISSILE_LAYERS, false);
+        assert_interacts(SPACESHIP_LAYERS, SPACESHIP_LAYERS, false);
+        assert_interacts(UFO_LAYERS, UFO_LAYERS, false);
+        assert_interacts(UFO_PROJECTILE_LAYERS, UFO_PROJECTILE_LAYERS, false);
+        assert_interacts(PICKUP_LAYERS, PICKUP_LAYERS, false);
+    }
+
+    #[test]
+    fn ufos_hit_everything_but_their_own_projectiles() {
+        assert_interacts(UFO_LAYERS, SPACESHIP_LAYERS, true);
+        assert_interacts(UFO_LAYERS, SPACESHIP_MISSILE_LAYERS, true);
+        assert_interacts(UFO_LAYERS, ASTEROID_LAYERS, true);
+        assert_interacts(UFO_LAYERS, UFO_PROJECTILE_LAYERS, false);
+        assert_interacts(UFO_PROJECTILE_LAYERS, SPACESHIP_LAYERS, true);
+        assert_interacts(UFO_PROJECTILE_LAYERS, ASTEROID_LAYERS, true);
+    }
+
+    #[test]
+    fn pickups_only_hit_the_spaceship() {
+        assert_interacts(PICKUP_LAYERS, SPACESHIP_LAYERS, true);
+        for layers in [SPACESHIP_MISSILE_LAYERS, ASTEROID_LAYERS, UFO_LAYERS, UFO_PROJECTILE_LAYERS] {
+            assert_interacts(PICKUP_LAYERS, layers, false);
+        }
+    }
+
+    #[test]
+    fn colliders_without_layers_hit_everything() {
+        for layers in [SPACESHIP_LAYERS, SPACESHIP_MISSILE_LAYERS, ASTEROID_LAYERS, UFO_LAYERS, UFO_PROJECTILE_LAYERS, PICKUP_LAYERS] {
+            assert_interacts(CollisionLayers::default(), layers, true);
+        }
+    }
+
+    fn collision_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((CollisionDetectionPlugin, HealthPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    fn spawn_collider(app: &mut App, translation: Vec3, bundle: impl Bundle) -> Entity {
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            Collider::new(1.0),
+            bundle,
+        )).id()
+    }
+
+    fn move_to(app: &mut App, entity: Entity, translation: Vec3) {
+        app.world.get_mut::<Transform>(entity).unwrap().translation = translation;
+    }
+
+    #[derive(Default)]
+    struct CollisionEvents {
+        started: EventCollector<CollisionStarted>,
+        lasting: EventCollector<CollisionEvent>,
+        ended: EventCollector<CollisionEnded>,
+    }
+
+    impl CollisionEvents {
+        /// Runs a tick and counts the started, lasting and ended collision events it sent.
+        fn tick(&mut self, app: &mut App) -> (usize, usize, usize) {
+            app.update();
+            (self.started.read(app).len(), self.lasting.read(app).len(), self.ended.read(app).len())
+        }
+    }
+
+    // contacts found at the end of a tick are handled at the start of the next one
+    #[test]
+    fn contacts_start_once_last_and_end_once() {
+        let mut app = collision_app();
+        spawn_collider(&mut app, Vec3::ZERO, ());
+        let other = spawn_collider(&mut app, Vec3::X, ());
+        let mut events = CollisionEvents::default();
+
+        assert_eq!(events.tick(&mut app), (0, 0, 0));
+        // one event for each of the two entities
+        assert_eq!(events.tick(&mut app), (2, 2, 0));
+        for _ in 0..3 {
+            assert_eq!(events.tick(&mut app), (0, 2, 0));
+        }
+
+        move_to(&mut app, other, Vec3::X * 10.0);
+        assert_eq!(events.tick(&mut app), (0, 2, 0));
+        assert_eq!(events.tick(&mut app), (0, 0, 2));
+        assert_eq!(events.tick(&mut app), (0, 0, 0));
+
+        move_to(&mut app, other, Vec3::X);
+        assert_eq!(events.tick(&mut app), (0, 0, 0));
+        assert_eq!(events.tick(&mut app), (2, 2, 0));
+    }
+
+    // an asteroid drifts over the right edge into the spaceship waiting just inside the left one
+    #[test]
+    fn contacts_carry_across_the_wrap_seam() {
+        let mut app = headless_app();
+        app
+            .add_plugins((CollisionDetectionPlugin, HealthPlugin, MovementPlugin, PlayAreaPlugin))
+            .insert_resource(PlayArea::wrapping());
+        start_game(&mut app);
+        let PlayAreaShape::Rectangle { half_extents } = PlayArea::wrapping().shape else {
+            unreachable!();
+        };
+        let edge = half_extents.x;
+        let spaceship = spawn_collider(&mut app, Vec3::X * (-edge + 0.5), SPACESHIP_LAYERS);
+        let asteroid = spawn_collider(&mut app, Vec3::X * (edge - 3.0), (ASTEROID_LAYERS, Velocity::new(Vec3::X * 6.0), Wraps));
+        let mut events = CollisionEvents::default();
+
+        let mut contacts = vec![];
+        let (mut started, mut ended) = (0, 0);
+        for _ in 0..60 {
+            let (tick_started, _, tick_ended) = events.tick(&mut app);
+            started += tick_started;
+            ended += tick_ended;
+            let x = app.world.get::<Transform>(asteroid).unwrap().translation.x;
+            if app.world.get::<Collider>(spaceship).unwrap().colliding_entities.contains(&asteroid) {
+                contacts.push(x);
+            }
+        }
+
+        // the contact starts while the asteroid only sticks out over the edge, and lasts while it wraps
+        assert!(contacts.first().is_some_and(|&x| x > edge - 1.05 && x < edge), "{:?}", contacts);
+        assert!(contacts.iter().any(|&x| x < 0.0), "{:?}", contacts);
+        assert!(contacts.windows(2).all(|pair| pair[1] > pair[0] || pair[1] < 0.0));
+        assert_eq!((started, ended), (2, 2));
+    }
+
+    #[test]
+    fn contacts_end_when_a_collider_is_despawned() {
+        let mut app = collision_app();
+        spawn_collider(&mut app, Vec3::ZERO, ());
+        let other = spawn_collider(&mut app, Vec3::X, ());
+        let mut events = CollisionEvents::default();
+        run_ticks(&mut app, 3);
+        events.tick(&mut app);
+
+        app.world.despawn(other);
+        let (_, _, first_ended) = events.tick(&mut app);
+        let (_, lasting, second_ended) = events.tick(&mut app);
+        assert_eq!(first_ended + second_ended, 2);
+        assert_eq!(lasting, 0);
+        assert_eq!(events.tick(&mut app), (0, 0, 0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lasting_contacts_deal_damage_once() {
+        let mut app = collision_app();
+        let entity = spawn_collider(&mut app, Vec3::ZERO, Health::new(100.0));
+        let other = spawn_collider(&mut app, Vec3::X, CollisionDamage::new(10.0));
+        run_ticks(&mut app, 10);
+        assert_eq!(app.world.get::<Health>(entity).unwrap().current, 90.0);
+
+        move_to(&mut app, other, Vec3::X * 10.0);
+        run_ticks(&mut app, 3);
+        move_to(&mut app, other, Vec3::X);
+        run_ticks(&mut app, 10);
+        assert_eq!(app.world.get::<Health>(entity).unwrap().current, 80.0);
+    }
+
+    #[test]
+    fn lasting_contacts_deal_damage_whenever_the_cooldown_runs_out() {
+        let mut app = collision_app();
+        let entity = spawn_collider(&mut app, Vec3::ZERO, (Health::new(100.0), DamageCooldown::new(0.1)));
+        spawn_collider(&mut app, Vec3::X, CollisionDamage::new(5.0));
+        run_ticks(&mut app, 2);
+        assert_eq!(app.world.get::<Health>(entity).unwrap().current, 95.0);
+
+        // a hit about every 0.1 seconds for the rest of the second
+        run_ticks(&mut app, 58);
+        let health = app.world.get::<Health>(entity).unwrap().current;
+        assert!((50.0..=60.0).contains(&health), "health {}", health);
+    }
+
+    /// Fires a missile that moves 10 units per tick, so it jumps right over an asteroid 1 unit wide,
+    /// and returns the asteroid's health afterwards.
+    fn fire_missile_over_asteroid(continuous: bool) -> f32 {
+        // a long tick, like on a frame hitch
+        let mut app = headless_app_with_tick_rate(5.0);
+        app.add_plugins((CollisionDetectionPlugin, HealthPlugin, MovementPlugin));
+        start_game(&mut app);
+
+        let asteroid = spawn_collider(&mut app, Vec3::ZERO, Health::new(100.0));
+        let missile = spawn_collider(
+            &mut app,
+            Vec3::new(-15.0, 0.0, 0.0),
+            (Velocity::new(Vec3::X * 50.0), CollisionDamage::new(10.0)),
+        );
+        if continuous {
+            app.world.entity_mut(missile).insert(ContinuousCollision::default());
+        }
+        app.world.get_mut::<Collider>(missile).unwrap().radius = 0.5;
+
+        run_ticks(&mut app, 4);
+        assert!(app.world.get::<Transform>(missile).unwrap().translation.x > 10.0);
+        app.world.get::<Health>(asteroid).unwrap().current
+    }
+
+    #[test]
+    fn continuous_collision_hits_what_a_long_tick_jumps_over() {
+        assert_eq!(fire_missile_over_asteroid(true), 90.0);
+    }
+
+    #[test]
+    fn discrete_collision_misses_what_a_long_tick_jumps_over() {
+        assert_eq!(fire_missile_over_asteroid(false), 100.0);
+    }
+}
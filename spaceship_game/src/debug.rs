@@ -1,11 +1,16 @@
 use bevy::prelude::*;
+use crate::collision_detection::{CollisionEnded, CollisionStarted};
+use crate::model_pool::ModelPool;
 use crate::schedule::InGameSet;
 
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, print_position.after(InGameSet::EntityUpdates));
+        app
+            .add_systems(FixedUpdate, (print_position, print_collisions).after(InGameSet::EntityUpdates))
+            .add_systems(Update, print_model_pool.run_if(resource_changed::<ModelPool>()))
+        ;
     }
 }
 
@@ -13,4 +18,18 @@ fn print_position(query: Query<(Entity, &Transform)>) {
     for (entity, transform) in query.iter() {
         info!("Entity {:?} is at position {:?},", entity, transform.translation);
     }
-}
\ No newline at end of file
+}
+
+fn print_collisions(mut started_reader: EventReader<CollisionStarted>,
+                    mut ended_reader: EventReader<CollisionEnded>) {
+    for started in started_reader.read() {
+        info!("Entity {:?} started colliding with {:?}", started.entity, started.collided_entity);
+    }
+    for ended in ended_reader.read() {
+        info!("Entity {:?} stopped colliding with {:?}", ended.entity, ended.collided_entity);
+    }
+}
+
+fn print_model_pool(model_pool: Res<ModelPool>) {
+    info!("Entities with a model spawned: {}", model_pool.spawned());
+}
@@ -1,43 +1,125 @@
 use bevy::prelude::*;
-use crate::health::Health;
+use crate::collision_detection::Collider;
+use crate::health::{Died, Health, Killed};
+use crate::model_pool::DespawnRecyclingExt;
+use crate::movement::Velocity;
+use crate::play_area::{PlayArea, Wraps};
+use crate::run_session::{ResetRun, RunSessionSet};
 use crate::schedule::InGameSet;
-use crate::state::GameState;
 
-const DESPAWN_DISTANCE: f32 = 100.0;
+/// Despawns the entity once the timer finishes.
+#[derive(Component, Debug)]
+pub struct Lifetime(pub Timer);
+
+impl Lifetime {
+    pub fn new(seconds: f32) -> Self {
+        Self(Timer::from_seconds(seconds, TimerMode::Once))
+    }
+}
 
 pub struct DespawnPlugin;
 
 impl Plugin for DespawnPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, (despawn_far_away_entities, despawn_dead_entities).in_set(InGameSet::DespawnEntities))
-            .add_systems(OnEnter(GameState::GameOver), despawn_all_entities)
+            .add_systems(
+                FixedUpdate,
+                (despawn_far_away_entities, despawn_expired_entities, despawn_dead_entities)
+                    .in_set(InGameSet::DespawnEntities),
+            )
+            .add_systems(ResetRun, despawn_all_entities.in_set(RunSessionSet::Cleanup))
         ;
     }
 }
 
+// only moving objects can leave the play area
 fn despawn_far_away_entities(mut commands: Commands,
-                             query: Query<(Entity, &GlobalTransform)>) {
-    for (entity, transform) in query.iter() {
-        let distance = transform.translation().distance(Vec3::ZERO);
-        if distance > DESPAWN_DISTANCE {
-            commands.entity(entity).despawn_recursive();
+                             play_area: Res<PlayArea>,
+                             query: Query<(Entity, &Transform, Has<Wraps>), With<Velocity>>) {
+    for (entity, transform, wraps) in query.iter() {
+        if wraps && play_area.wraps() {
+            continue;
+        }
+        if !play_area.contains(transform.translation) {
+            commands.entity(entity).despawn_recycling();
+        }
+    }
+}
+
+fn despawn_expired_entities(mut commands: Commands,
+                            mut query: Query<(Entity, &mut Lifetime)>,
+                            time: Res<Time>) {
+    for (entity, mut lifetime) in query.iter_mut() {
+        lifetime.0.tick(time.delta());
+        if lifetime.0.finished() {
+            commands.entity(entity).despawn_recycling();
         }
     }
 }
 
-fn despawn_dead_entities(mut commands: Commands,
-                         query: Query<(Entity, &Health)>) {
+pub fn despawn_dead_entities(mut commands: Commands,
+                             mut died_writer: EventWriter<Died>,
+                             mut killed_writer: EventWriter<Killed>,
+                             query: Query<(Entity, &Health)>) {
     for (entity, health) in query.iter() {
-        if health.value <= 0.0 {
-            commands.entity(entity).despawn_recursive();
+        if health.is_dead() {
+            died_writer.send(Died { entity, killer: health.last_damaged_by });
+            if let Some(killer) = health.last_damaged_by {
+                killed_writer.send(Killed { victim: entity, killer });
+            }
+            commands.entity(entity).despawn_recycling();
         }
     }
 }
 
+// every gameplay object has a collider, anything else (camera, UI) outlives the run
 fn despawn_all_entities(mut commands: Commands,
-                        query: Query<(Entity), With<Health>>) {
-    for (entity) in query.iter() {
-        commands.entity(entity).despawn_recursive();
+                        query: Query<Entity, With<Collider>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recycling();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::health::HealthPlugin;
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    fn despawn_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((DespawnPlugin, HealthPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    #[test]
+    fn entities_despawn_once_their_lifetime_runs_out() {
+        let mut app = despawn_app();
+        let short = app.world.spawn(Lifetime::new(0.5)).id();
+        let long = app.world.spawn(Lifetime::new(1.0)).id();
+
+        run_ticks(&mut app, 29);
+        assert!(app.world.get_entity(short).is_some());
+        run_ticks(&mut app, 2);
+        assert!(app.world.get_entity(short).is_none());
+        assert!(app.world.get_entity(long).is_some());
+        run_ticks(&mut app, 30);
+        assert!(app.world.get_entity(long).is_none());
+    }
+
+    #[test]
+    fn moving_entities_despawn_outside_the_play_area() {
+        let mut app = despawn_app();
+        let spawn = |app: &mut App, translation: Vec3| app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            Velocity::new(Vec3::ZERO),
+        )).id();
+        let inside = spawn(&mut app, Vec3::new(50.0, 0.0, 0.0));
+        let outside = spawn(&mut app, Vec3::new(0.0, 0.0, 150.0));
+        assert!(!app.world.resource::<PlayArea>().contains(Vec3::new(0.0, 0.0, 150.0)));
+
+        run_ticks(&mut app, 1);
+        assert!(app.world.get_entity(inside).is_some());
+        assert!(app.world.get_entity(outside).is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use crate::collision_detection::{Collider, CollisionDamage, CollisionLayers, Owner};
+use crate::health::Health;
+use crate::model_pool::{Model, Models};
+use crate::movement::{update_velocity, Acceleration, MovingObjectBundle, Velocity};
+use crate::play_area::Wraps;
+use crate::schedule::InGameSet;
+use crate::score::ScoreValue;
+use crate::spaceship::Spaceship;
+use crate::waves::Hostile;
+use crate::weapons::{spawn_projectiles, Weapon, WeaponKind};
+
+const UFO_RADIUS: f32 = 3.0;
+const UFO_SCALE: f32 = 0.6;
+const UFO_HEALTH: f32 = 30.0;
+const UFO_COLLISION_DAMAGE: f32 = 25.0;
+const UFO_POINTS: u32 = 150;
+const UFO_MAX_SPEED: f32 = 12.0;
+const UFO_MAX_STEERING: f32 = 20.0;
+/// Distance the UFO tries to keep from the spaceship, it seeks when further and backs off when closer.
+const UFO_PREFERRED_DISTANCE: f32 = 30.0;
+/// Band around the preferred distance in which the UFO only strafes.
+const UFO_DISTANCE_TOLERANCE: f32 = 5.0;
+const UFO_STRAFE_SPEED: f32 = 8.0;
+const UFO_FIRE_RANGE: f32 = 45.0;
+
+pub const UFO_LAYERS: CollisionLayers = CollisionLayers::new(
+    CollisionLayers::ENEMY,
+    !(CollisionLayers::ENEMY | CollisionLayers::ENEMY_PROJECTILE),
+);
+pub const UFO_PROJECTILE_LAYERS: CollisionLayers = CollisionLayers::new(
+    CollisionLayers::ENEMY_PROJECTILE,
+    !(CollisionLayers::ENEMY | CollisionLayers::ENEMY_PROJECTILE),
+);
+
+#[derive(Component, Debug)]
+pub struct Ufo {
+    /// 1 to circle the spaceship counter-clockwise, -1 for clockwise.
+    pub strafe_direction: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct UfoProjectile;
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(
+                FixedUpdate,
+                (steer_ufos.before(update_velocity), ufo_weapons)
+                    .chain()
+                    .in_set(InGameSet::EntityUpdates),
+            )
+        ;
+    }
+}
+
+pub fn spawn_ufo(commands: &mut Commands,
+                 models: &mut Models,
+                 translation: Vec3,
+                 strafe_direction: f32) {
+    models.spawn(commands, Model::Ufo, (
+        MovingObjectBundle {
+            velocity: Velocity::new(Vec3::ZERO),
+            acceleration: Acceleration::new(Vec3::ZERO),
+            collider: Collider::new(UFO_RADIUS),
+            spatial: SpatialBundle::from_transform(
+                Transform::from_translation(translation).with_scale(Vec3::splat(UFO_SCALE)),
+            ),
+        },
+        Ufo { strafe_direction },
+        Hostile,
+        Wraps,
+        ScoreValue(UFO_POINTS),
+        Health::new(UFO_HEALTH),
+        CollisionDamage::new(UFO_COLLISION_DAMAGE),
+        UFO_LAYERS,
+        Weapon::new(WeaponKind::UfoBlaster),
+    ));
+}
+
+/// Seeks the spaceship from afar, backs off when too close and strafes around it in between,
+/// always turning to face it. Steering is capped, so UFOs swing around rather than turn on the spot.
+fn steer_ufos(mut query: Query<(&mut Transform, &mut Acceleration, &mut Velocity, &Ufo), Without<Spaceship>>,
+              spaceship_query: Query<&Transform, With<Spaceship>>) {
+    let Ok(spaceship_transform) = spaceship_query.get_single() else {
+        for (_, mut acceleration, _, _) in query.iter_mut() {
+            acceleration.value = Vec3::ZERO;
+        }
+        return;
+    };
+
+    for (mut transform, mut acceleration, mut velocity, ufo) in query.iter_mut() {
+        let to_spaceship = spaceship_transform.translation - transform.translation;
+        let distance = to_spaceship.length();
+        let direction = to_spaceship.normalize_or_zero();
+
+        let approach = if distance > UFO_PREFERRED_DISTANCE + UFO_DISTANCE_TOLERANCE {
+            direction * UFO_MAX_SPEED
+        } else if distance < UFO_PREFERRED_DISTANCE - UFO_DISTANCE_TOLERANCE {
+            -direction * UFO_MAX_SPEED
+        } else {
+            Vec3::ZERO
+        };
+        let strafe = Vec3::Y.cross(direction) * ufo.strafe_direction * UFO_STRAFE_SPEED;
+        let desired_velocity = (approach + strafe).clamp_length_max(UFO_MAX_SPEED);
+
+        acceleration.value = (desired_velocity - velocity.value).clamp_length_max(UFO_MAX_STEERING);
+        velocity.value = velocity.value.clamp_length_max(UFO_MAX_SPEED);
+
+        // projectiles leave along -forward, like the spaceship's
+        if direction != Vec3::ZERO {
+            let target = transform.translation - direction;
+            transform.look_at(target, Vec3::Y);
+        }
+    }
+}
+
+fn ufo_weapons(mut commands: Commands,
+               mut models: Models,
+               mut query: Query<(Entity, &Transform, &mut Weapon), With<Ufo>>,
+               spaceship_query: Query<&Transform, With<Spaceship>>,
+               time: Res<Time>) {
+    let Ok(spaceship_transform) = spaceship_query.get_single() else {
+        return;
+    };
+    for (entity, transform, mut weapon) in query.iter_mut() {
+        weapon.tick(time.delta());
+        if transform.translation.distance(spaceship_transform.translation) > UFO_FIRE_RANGE {
+            continue;
+        }
+        if weapon.try_fire() {
+            spawn_projectiles(&mut commands, &mut models, &weapon, transform, UFO_PROJECTILE_LAYERS, (UfoProjectile, Owner(entity)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::movement::MovementPlugin;
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    const TICKS_PER_SECOND: f32 = 60.0;
+
+    /// App with a spaceship sitting still at the origin, UFOs only move with `MovementPlugin`.
+    fn ufo_app(moving: bool) -> App {
+        let mut app = headless_app();
+        app.add_plugins(EnemyPlugin);
+        if moving {
+            app.add_plugins(MovementPlugin);
+        }
+        start_game(&mut app);
+        app.world.spawn((Spaceship, TransformBundle::default()));
+        app
+    }
+
+    fn spawn(app: &mut App, translation: Vec3, strafe_direction: f32) -> Entity {
+        app.world.run_system_once(move |mut commands: Commands, mut models: Models| {
+            spawn_ufo(&mut commands, &mut models, translation, strafe_direction);
+        });
+        app.world.query_filtered::<Entity, With<Ufo>>().single(&app.world)
+    }
+
+    fn run_seconds(app: &mut App, seconds: f32) {
+        run_ticks(app, (seconds * TICKS_PER_SECOND).round() as usize);
+    }
+
+    fn translation_and_velocity(app: &App, ufo: Entity) -> (Vec3, Vec3) {
+        (app.world.get::<Transform>(ufo).unwrap().translation, app.world.get::<Velocity>(ufo).unwrap().value)
+    }
+
+    #[test]
+    fn ufos_seek_the_spaceship_from_afar_and_face_it() {
+        let mut app = ufo_app(true);
+        let ufo = spawn(&mut app, Vec3::X * 60.0, 1.0);
+        run_seconds(&mut app, 1.0);
+
+        let (translation, velocity) = translation_and_velocity(&app, ufo);
+        assert!(translation.length() < 60.0 - UFO_MAX_SPEED / 4.0, "{:?}", translation);
+        assert!(velocity.dot(-translation) > 0.0, "{:?}", velocity);
+        assert!(velocity.length() <= UFO_MAX_SPEED + 0.01);
+        let facing = -app.world.get::<Transform>(ufo).unwrap().forward();
+        assert!(facing.angle_between(-translation) < 0.01, "{:?}", facing);
+    }
+
+    #[test]
+    fn ufos_back_off_when_too_close() {
+        let mut app = ufo_app(true);
+        let ufo = spawn(&mut app, Vec3::X * 10.0, 1.0);
+        run_seconds(&mut app, 1.0);
+
+        let (translation, velocity) = translation_and_velocity(&app, ufo);
+        assert!(translation.length() > 10.0 + UFO_MAX_SPEED / 4.0, "{:?}", translation);
+        assert!(velocity.dot(translation) > 0.0, "{:?}", velocity);
+    }
+
+    #[test]
+    fn ufos_strafe_around_the_spaceship_at_the_preferred_distance() {
+        for strafe_direction in [1.0, -1.0] {
+            let mut app = ufo_app(true);
+            let ufo = spawn(&mut app, Vec3::X * UFO_PREFERRED_DISTANCE, strafe_direction);
+            run_seconds(&mut app, 0.5);
+
+            let (translation, velocity) = translation_and_velocity(&app, ufo);
+            let distance = translation.length();
+            assert!((distance - UFO_PREFERRED_DISTANCE).abs() < UFO_DISTANCE_TOLERANCE, "{}", distance);
+            // seen from above, counter-clockwise around the spaceship is towards +z from +x
+            let tangent = Vec3::Y.cross(-translation.normalize()) * strafe_direction;
+            assert!(velocity.normalize().dot(tangent) > 0.95, "{:?}", velocity);
+        }
+    }
+
+    fn ufo_projectiles(app: &mut App) -> usize {
+        app.world.query_filtered::<(), With<UfoProjectile>>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn ufos_fire_at_their_fire_rate_within_range_only() {
+        let fire_rate = Weapon::new(WeaponKind::UfoBlaster).fire_rate;
+
+        let mut app = ufo_app(false);
+        spawn(&mut app, Vec3::X * (UFO_FIRE_RANGE - 1.0), 1.0);
+        // the first shot right away, then one per cooldown
+        run_seconds(&mut app, 2.5 / fire_rate);
+        assert_eq!(ufo_projectiles(&mut app), 3);
+
+        let mut app = ufo_app(false);
+        spawn(&mut app, Vec3::X * (UFO_FIRE_RANGE + 1.0), 1.0);
+        run_seconds(&mut app, 2.5 / fire_rate);
+        assert_eq!(ufo_projectiles(&mut app), 0);
+    }
+}
@@ -1,13 +1,256 @@
+use std::time::Duration;
 use bevy::prelude::*;
-
+use crate::collision_detection::apply_collision_damage;
+use crate::schedule::InGameSet;
 
 #[derive(Component, Debug)]
 pub struct Health {
-    pub value: f32,
+    pub current: f32,
+    pub max: f32,
+    /// Health restored per second, zero for none.
+    pub regeneration: f32,
+    /// Window after taking damage during which further damage from any source is ignored.
+    invulnerability: Option<Timer>,
+    /// Entity credited with the most recent damage, e.g. for scoring a kill.
+    pub last_damaged_by: Option<Entity>,
+    /// Health regenerated since it was last full, reported as one `Healed` once it's full again.
+    regenerated: f32,
 }
 
 impl Health {
-    pub fn new(value: f32) -> Self {
-        Self { value }
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regeneration: 0.0,
+            invulnerability: None,
+            last_damaged_by: None,
+            regenerated: 0.0,
+        }
+    }
+
+    pub fn with_regeneration(mut self, per_second: f32) -> Self {
+        self.regeneration = per_second;
+        self
+    }
+
+    pub fn with_invulnerability(mut self, seconds: f32) -> Self {
+        let mut timer = Timer::from_seconds(seconds, TimerMode::Once);
+        timer.tick(Duration::from_secs_f32(seconds));
+        self.invulnerability = Some(timer);
+        self
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerability.as_ref().is_some_and(|timer| !timer.finished())
+    }
+
+    /// Returns the damage actually taken, zero while invulnerable.
+    pub fn take_damage(&mut self, amount: f32, source: Entity) -> f32 {
+        if self.is_invulnerable() {
+            return 0.0;
+        }
+        if let Some(timer) = &mut self.invulnerability {
+            timer.reset();
+        }
+        self.current -= amount;
+        self.last_damaged_by = Some(source);
+        amount
+    }
+
+    /// Returns the health actually restored, which is capped at `max`.
+    pub fn heal(&mut self, amount: f32) -> f32 {
+        let healed = amount.min(self.max - self.current).max(0.0);
+        self.current += healed;
+        healed
+    }
+}
+
+#[derive(Event, Debug)]
+pub struct DamageTaken {
+    pub entity: Entity,
+    pub amount: f32,
+}
+
+/// Sent for discrete heals such as repair pickups, and for regeneration once it filled health back up.
+#[derive(Event, Debug)]
+pub struct Healed {
+    pub entity: Entity,
+    pub amount: f32,
+}
+
+/// Sent when an entity's health runs out, before it is despawned.
+#[derive(Event, Debug)]
+pub struct Died {
+    pub entity: Entity,
+    /// Entity credited with the kill, if any.
+    pub killer: Option<Entity>,
+}
+
+/// Sent along with `Died` when something is credited with the kill.
+#[derive(Event, Debug)]
+pub struct Killed {
+    pub victim: Entity,
+    pub killer: Entity,
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<DamageTaken>()
+            .add_event::<Healed>()
+            .add_event::<Died>()
+            .add_event::<Killed>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_invulnerability.before(apply_collision_damage),
+                    regenerate_health,
+                )
+                    .in_set(InGameSet::EntityUpdates),
+            )
+        ;
+    }
+}
+
+fn tick_invulnerability(mut query: Query<&mut Health>, time: Res<Time>) {
+    for mut health in query.iter_mut() {
+        if let Some(timer) = &mut health.invulnerability {
+            timer.tick(time.delta());
+        }
+    }
+}
+
+fn regenerate_health(mut healed_writer: EventWriter<Healed>,
+                     mut query: Query<(Entity, &mut Health)>,
+                     time: Res<Time>) {
+    for (entity, mut health) in query.iter_mut() {
+        if health.regeneration <= 0.0 || health.is_dead() {
+            continue;
+        }
+        if health.current < health.max {
+            let regenerated = health.regeneration * time.delta_seconds();
+            let amount = health.heal(regenerated);
+            health.regenerated += amount;
+        }
+        // a single event once regeneration stops instead of one every tick
+        if health.current >= health.max && health.regenerated > 0.0 {
+            healed_writer.send(Healed { entity, amount: health.regenerated });
+            health.regenerated = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collision_detection::{Collider, CollisionDamage, CollisionDetectionPlugin};
+    use crate::despawn::DespawnPlugin;
+    use crate::testing::{headless_app, run_ticks, start_game, EventCollector};
+    use super::*;
+
+    fn health_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((HealthPlugin, CollisionDetectionPlugin, DespawnPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    fn spawn(app: &mut App, bundle: impl Bundle) -> Entity {
+        app.world.spawn((TransformBundle::default(), Collider::new(1.0), bundle)).id()
+    }
+
+    fn current(app: &App, entity: Entity) -> f32 {
+        app.world.get::<Health>(entity).unwrap().current
+    }
+
+    #[test]
+    fn healing_is_capped_at_max() {
+        let mut health = Health::new(100.0);
+        health.current = 90.0;
+        assert_eq!(health.heal(5.0), 5.0);
+        assert_eq!(health.heal(20.0), 5.0);
+        assert_eq!(health.heal(20.0), 0.0);
+        assert_eq!(health.current, 100.0);
+    }
+
+    #[test]
+    fn damage_is_ignored_while_invulnerable() {
+        let source = Entity::from_raw(1);
+        let mut health = Health::new(100.0).with_invulnerability(1.0);
+        assert!(!health.is_invulnerable());
+        assert_eq!(health.take_damage(30.0, source), 30.0);
+        assert!(health.is_invulnerable());
+        assert_eq!(health.take_damage(30.0, source), 0.0);
+        assert_eq!(health.current, 70.0);
+        assert_eq!(health.last_damaged_by, Some(source));
+    }
+
+    #[test]
+    fn invulnerability_runs_out() {
+        let mut app = health_app();
+        let entity = app.world.spawn(Health::new(100.0).with_invulnerability(0.5)).id();
+        app.world.get_mut::<Health>(entity).unwrap().take_damage(10.0, Entity::from_raw(1));
+
+        run_ticks(&mut app, 25);
+        assert!(app.world.get::<Health>(entity).unwrap().is_invulnerable());
+        run_ticks(&mut app, 6);
+        assert!(!app.world.get::<Health>(entity).unwrap().is_invulnerable());
+    }
+
+    #[test]
+    fn regeneration_sends_one_healed_once_health_is_full() {
+        let mut app = health_app();
+        let mut healed = EventCollector::<Healed>::default();
+        let mut health = Health::new(100.0).with_regeneration(10.0);
+        health.current = 50.0;
+        let entity = app.world.spawn(health).id();
+
+        run_ticks(&mut app, 60);
+        assert!((current(&app, entity) - 60.0).abs() < 0.01);
+        assert!(healed.read(&app).is_empty());
+        let mut events = vec![];
+        for _ in 0..600 {
+            run_ticks(&mut app, 1);
+            events.extend(healed.read(&app).iter().map(|healed| (healed.entity, healed.amount)));
+        }
+        assert_eq!(current(&app, entity), 100.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, entity);
+        assert!((events[0].1 - 50.0).abs() < 0.01, "healed {}", events[0].1);
+
+        run_ticks(&mut app, 60);
+        assert!(healed.read(&app).is_empty());
+    }
+
+    #[test]
+    fn collisions_send_damage_taken() {
+        let mut app = health_app();
+        let mut damage = EventCollector::<DamageTaken>::default();
+        let target = spawn(&mut app, Health::new(100.0));
+        spawn(&mut app, CollisionDamage::new(30.0));
+        run_ticks(&mut app, 2);
+
+        let damage: Vec<(Entity, f32)> = damage.read(&app).iter().map(|damage| (damage.entity, damage.amount)).collect();
+        assert_eq!(damage, vec![(target, 30.0)]);
+        assert_eq!(current(&app, target), 70.0);
+    }
+
+    #[test]
+    fn running_out_of_health_sends_died_with_the_killer() {
+        let mut app = health_app();
+        let mut died = EventCollector::<Died>::default();
+        let target = spawn(&mut app, Health::new(20.0));
+        let killer = spawn(&mut app, CollisionDamage::new(30.0));
+        run_ticks(&mut app, 3);
+
+        let died: Vec<(Entity, Option<Entity>)> = died.read(&app).iter().map(|died| (died.entity, died.killer)).collect();
+        assert_eq!(died, vec![(target, Some(killer))]);
+        assert!(app.world.get_entity(target).is_none());
     }
 }
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use crate::health::{Healed, Health};
+use crate::score::Score;
+use crate::shield::ShieldEnergy;
+use crate::spaceship::Spaceship;
+use crate::state::GameState;
+use crate::waves::{WaveCleared, WaveStarted};
+
+const HUD_MARGIN: f32 = 16.0;
+const BAR_WIDTH: f32 = 200.0;
+const BAR_HEIGHT: f32 = 14.0;
+const BAR_BACKGROUND_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const HEALTH_BAR_COLOR: Color = Color::rgb(0.85, 0.2, 0.25);
+const SHIELD_BAR_COLOR: Color = Color::rgb(0.3, 0.6, 1.0);
+const HEAL_FLASH_COLOR: Color = Color::rgb(0.5, 1.0, 0.6);
+/// Flash per share of max health healed, so healing half of it flashes fully.
+const HEAL_FLASH_PER_HEALTH_SHARE: f32 = 2.0;
+const HEAL_FLASH_DECAY_PER_SECOND: f32 = 2.0;
+const FONT_SIZE: f32 = 24.0;
+
+/// Root of a HUD widget, hidden while a menu screen is shown.
+#[derive(Component, Debug)]
+pub struct Hud;
+
+#[derive(Component, Debug)]
+struct HealthBar;
+
+#[derive(Component, Debug)]
+struct ShieldBar;
+
+#[derive(Component, Debug)]
+struct ScoreText;
+
+#[derive(Component, Debug)]
+struct WaveText;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    update_health_bar,
+                    flash_health_bar,
+                    update_shield_bar,
+                    update_score_text,
+                    update_wave_text,
+                    show_hud.run_if(state_changed::<GameState>()),
+                ),
+            )
+        ;
+    }
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(HUD_MARGIN),
+                    left: Val::Px(HUD_MARGIN),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(HUD_MARGIN / 2.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Hud,
+        ))
+        .with_children(|parent| {
+            spawn_bar(parent, HEALTH_BAR_COLOR, HealthBar);
+            spawn_bar(parent, SHIELD_BAR_COLOR, ShieldBar);
+            parent.spawn((text_bundle("Score: 0"), ScoreText));
+            parent.spawn((text_bundle(""), WaveText));
+        });
+}
+
+fn spawn_bar(parent: &mut ChildBuilder, color: Color, marker: impl Component) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(BAR_HEIGHT),
+                ..default()
+            },
+            background_color: BAR_BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                marker,
+            ));
+        });
+}
+
+fn text_bundle(value: &str) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size: FONT_SIZE,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
+
+/// Width of a bar's fill, as a percentage of the bar.
+fn fill_width(current: f32, max: f32) -> Val {
+    Val::Percent((current / max).clamp(0.0, 1.0) * 100.0)
+}
+
+fn update_health_bar(spaceship_query: Query<&Health, (With<Spaceship>, Changed<Health>)>,
+                     mut bar_query: Query<&mut Style, With<HealthBar>>) {
+    let Ok(health) = spaceship_query.get_single() else {
+        return;
+    };
+    for mut style in bar_query.iter_mut() {
+        style.width = fill_width(health.current, health.max);
+    }
+}
+
+/// Flashes the health bar when the ship is healed, brighter the more it was healed.
+fn flash_health_bar(mut healed_reader: EventReader<Healed>,
+                    mut flash: Local<f32>,
+                    spaceship_query: Query<&Health, With<Spaceship>>,
+                    mut bar_query: Query<&mut BackgroundColor, With<HealthBar>>,
+                    time: Res<Time>) {
+    let was_flashing = *flash > 0.0;
+    for healed in healed_reader.read() {
+        if let Ok(health) = spaceship_query.get(healed.entity) {
+            *flash += healed.amount / health.max * HEAL_FLASH_PER_HEALTH_SHARE;
+        }
+    }
+    if !was_flashing && *flash <= 0.0 {
+        return;
+    }
+    *flash = (*flash - HEAL_FLASH_DECAY_PER_SECOND * time.delta_seconds()).clamp(0.0, 1.0);
+
+    let base = Vec4::from(HEALTH_BAR_COLOR.as_rgba_f32());
+    let flashed = Vec4::from(HEAL_FLASH_COLOR.as_rgba_f32());
+    let color = base.lerp(flashed, *flash);
+    for mut background_color in bar_query.iter_mut() {
+        *background_color = Color::rgba(color.x, color.y, color.z, color.w).into();
+    }
+}
+
+fn update_shield_bar(spaceship_query: Query<&ShieldEnergy, (With<Spaceship>, Changed<ShieldEnergy>)>,
+                     mut bar_query: Query<&mut Style, With<ShieldBar>>) {
+    let Ok(energy) = spaceship_query.get_single() else {
+        return;
+    };
+    for mut style in bar_query.iter_mut() {
+        style.width = fill_width(energy.current, energy.max);
+    }
+}
+
+fn update_score_text(score: Res<Score>,
+                     mut text_query: Query<&mut Text, With<ScoreText>>) {
+    if !score.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match score.combo_multiplier {
+            1 => format!("Score: {}", score.points),
+            multiplier => format!("Score: {}  x{}", score.points, multiplier),
+        };
+    }
+}
+
+fn update_wave_text(mut started_reader: EventReader<WaveStarted>,
+                    mut cleared_reader: EventReader<WaveCleared>,
+                    mut text_query: Query<&mut Text, With<WaveText>>) {
+    let started = started_reader.read().last().map(|started| format!("Wave {}", started.wave));
+    let cleared = cleared_reader.read().last().map(|cleared| format!("Wave {} cleared", cleared.wave));
+    // the next wave only starts after a gap, so if both happened this frame the start is the newer one
+    let Some(value) = started.or(cleared) else {
+        return;
+    };
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+// menu screens take the screen on their own
+fn show_hud(state: Res<State<GameState>>,
+            mut hud_query: Query<&mut Visibility, With<Hud>>) {
+    for mut visibility in hud_query.iter_mut() {
+        *visibility = match state.get() {
+            GameState::InGame => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
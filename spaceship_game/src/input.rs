@@ -0,0 +1,341 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::schedule::InGameSet;
+
+const BINDINGS_PATH: &str = "bindings.ron";
+/// Analog axis values closer to zero than this are ignored.
+const AXIS_DEADZONE: f32 = 0.2;
+
+/// Ship controls for the current tick, read by the spaceship systems instead of the keyboard,
+/// so they can also be fed from a replay.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipInput {
+    pub thrust: f32,
+    pub turn: f32,
+    pub roll: f32,
+    pub fire: bool,
+    pub shield: bool,
+    pub cycle_weapon: bool,
+}
+
+/// Systems that fill `ShipInput`, ordered before everything that reads it.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct ShipInputSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShipAction {
+    Thrust,
+    Turn,
+    Roll,
+    Fire,
+    Shield,
+    CycleWeapon,
+    Pause,
+    // menu actions, the ship never reads them
+    MenuUp,
+    MenuDown,
+    MenuConfirm,
+    MenuBack,
+}
+
+/// Physical input an action is bound to. Single keys and buttons read as 0 or 1,
+/// pairs read as -1, 0 or 1 and gamepad axes anywhere in between.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Keys { negative: KeyCode, positive: KeyCode },
+    Button(GamepadButtonType),
+    Buttons { negative: GamepadButtonType, positive: GamepadButtonType },
+    Axis { axis: GamepadAxisType, inverted: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionBindings {
+    pub action: ShipAction,
+    pub bindings: Vec<Binding>,
+}
+
+/// Bindings of every action, loaded from `BindingsPath` and saved back when changed in the settings screen.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bindings {
+    pub actions: Vec<ActionBindings>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let action = |action, bindings: &[Binding]| ActionBindings { action, bindings: bindings.to_vec() };
+        Self {
+            actions: vec![
+                action(ShipAction::Thrust, &[
+                    Binding::Keys { negative: KeyCode::S, positive: KeyCode::W },
+                    Binding::Axis { axis: GamepadAxisType::LeftStickY, inverted: false },
+                ]),
+                action(ShipAction::Turn, &[
+                    Binding::Keys { negative: KeyCode::D, positive: KeyCode::A },
+                    Binding::Axis { axis: GamepadAxisType::LeftStickX, inverted: true },
+                ]),
+                action(ShipAction::Roll, &[
+                    Binding::Keys { negative: KeyCode::ShiftLeft, positive: KeyCode::ControlLeft },
+                    Binding::Buttons { negative: GamepadButtonType::LeftTrigger, positive: GamepadButtonType::RightTrigger },
+                ]),
+                action(ShipAction::Fire, &[
+                    Binding::Key(KeyCode::Space),
+                    Binding::Button(GamepadButtonType::RightTrigger2),
+                ]),
+                action(ShipAction::Shield, &[
+                    Binding::Key(KeyCode::Tab),
+                    Binding::Button(GamepadButtonType::LeftTrigger2),
+                ]),
+                action(ShipAction::CycleWeapon, &[
+                    Binding::Key(KeyCode::Q),
+                    Binding::Button(GamepadButtonType::North),
+                ]),
+                action(ShipAction::Pause, &[
+                    Binding::Key(KeyCode::Escape),
+                    Binding::Button(GamepadButtonType::Start),
+                ]),
+                action(ShipAction::MenuUp, &[
+                    Binding::Key(KeyCode::Up),
+                    Binding::Key(KeyCode::W),
+                    Binding::Button(GamepadButtonType::DPadUp),
+                ]),
+                action(ShipAction::MenuDown, &[
+                    Binding::Key(KeyCode::Down),
+                    Binding::Key(KeyCode::S),
+                    Binding::Button(GamepadButtonType::DPadDown),
+                ]),
+                action(ShipAction::MenuConfirm, &[
+                    Binding::Key(KeyCode::Return),
+                    Binding::Key(KeyCode::Space),
+                    Binding::Button(GamepadButtonType::South),
+                ]),
+                action(ShipAction::MenuBack, &[
+                    Binding::Key(KeyCode::Escape),
+                    Binding::Button(GamepadButtonType::East),
+                ]),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn of(&self, action: ShipAction) -> &[Binding] {
+        self.actions
+            .iter()
+            .find(|action_bindings| action_bindings.action == action)
+            .map_or(&[], |action_bindings| &action_bindings.bindings)
+    }
+
+    /// Keyboard key bound to `action` on the side of `sign`, a positive sign for single keys.
+    pub fn key(&self, action: ShipAction, sign: f32) -> Option<KeyCode> {
+        self.of(action).iter().find_map(|binding| match *binding {
+            Binding::Key(key) if sign > 0.0 => Some(key),
+            Binding::Keys { negative, .. } if sign < 0.0 => Some(negative),
+            Binding::Keys { positive, .. } if sign > 0.0 => Some(positive),
+            _ => None,
+        })
+    }
+
+    /// Gamepad button bound to `action` on the side of `sign`, a positive sign for single buttons.
+    pub fn button(&self, action: ShipAction, sign: f32) -> Option<GamepadButtonType> {
+        self.of(action).iter().find_map(|binding| match *binding {
+            Binding::Button(button) if sign > 0.0 => Some(button),
+            Binding::Buttons { negative, .. } if sign < 0.0 => Some(negative),
+            Binding::Buttons { positive, .. } if sign > 0.0 => Some(positive),
+            _ => None,
+        })
+    }
+
+    /// Gamepad axis bound to `action`, whichever side.
+    pub fn axis(&self, action: ShipAction) -> Option<GamepadAxisType> {
+        self.of(action).iter().find_map(|binding| match *binding {
+            Binding::Axis { axis, .. } => Some(axis),
+            _ => None,
+        })
+    }
+
+    /// Rebinds the keyboard key returned by `key`, adding a keyboard binding if the action has none.
+    pub fn set_key(&mut self, action: ShipAction, sign: f32, key: KeyCode) {
+        let bindings = self.bindings_mut(action);
+        for binding in bindings.iter_mut() {
+            match binding {
+                Binding::Key(bound) if sign > 0.0 => *bound = key,
+                Binding::Keys { negative, .. } if sign < 0.0 => *negative = key,
+                Binding::Keys { positive, .. } if sign > 0.0 => *positive = key,
+                _ => continue,
+            }
+            return;
+        }
+        bindings.push(Binding::Key(key));
+    }
+
+    /// Rebinds the gamepad button returned by `button`. Without one, the positive side gets a single button
+    /// and the negative side is paired with the positive side's button. Returns `false` if there's none to pair with.
+    pub fn set_button(&mut self, action: ShipAction, sign: f32, button: GamepadButtonType) -> bool {
+        let bindings = self.bindings_mut(action);
+        for binding in bindings.iter_mut() {
+            match *binding {
+                Binding::Button(_) if sign > 0.0 => *binding = Binding::Button(button),
+                Binding::Button(positive) if sign < 0.0 => *binding = Binding::Buttons { negative: button, positive },
+                Binding::Buttons { positive, .. } if sign < 0.0 => *binding = Binding::Buttons { negative: button, positive },
+                Binding::Buttons { negative, .. } if sign > 0.0 => *binding = Binding::Buttons { negative, positive: button },
+                _ => continue,
+            }
+            return true;
+        }
+        if sign < 0.0 {
+            return false;
+        }
+        bindings.push(Binding::Button(button));
+        true
+    }
+
+    fn bindings_mut(&mut self, action: ShipAction) -> &mut Vec<Binding> {
+        let index = match self.actions.iter().position(|action_bindings| action_bindings.action == action) {
+            Some(index) => index,
+            None => {
+                self.actions.push(ActionBindings { action, bindings: vec![] });
+                self.actions.len() - 1
+            }
+        };
+        &mut self.actions[index].bindings
+    }
+
+    /// Loads the bindings saved at `path`. Actions missing from the file, e.g. ones added since it was saved,
+    /// keep their default bindings.
+    pub fn load(path: &Path) -> Self {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let mut bindings: Self = ron::from_str(&contents).unwrap_or_else(|err| {
+            warn!("Ignoring invalid bindings in {}: {}", path.display(), err);
+            Self::default()
+        });
+        for defaults in Self::default().actions {
+            if !bindings.actions.iter().any(|action_bindings| action_bindings.action == defaults.action) {
+                bindings.actions.push(defaults);
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(path, contents).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            error!("Failed to save bindings to {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// File the bindings are loaded from and saved to, without one they're the defaults and changes only last while the game runs.
+#[derive(Resource, Debug, Clone)]
+pub struct BindingsPath(pub Option<PathBuf>);
+
+/// Reads actions through the current `Bindings`, from the keyboard and every connected gamepad.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    bindings: Res<'w, Bindings>,
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl ActionInput<'_> {
+    /// Value of the action between -1 and 1, the strongest of its bindings wins.
+    pub fn value(&self, action: ShipAction) -> f32 {
+        self.bindings
+            .of(action)
+            .iter()
+            .map(|binding| self.binding_value(binding))
+            .fold(0.0, |strongest, value| if value.abs() > strongest.abs() { value } else { strongest })
+    }
+
+    pub fn pressed(&self, action: ShipAction) -> bool {
+        self.value(action) != 0.0
+    }
+
+    /// Whether a key or button of the action went down this frame, axes never count.
+    pub fn just_pressed(&self, action: ShipAction) -> bool {
+        self.bindings.of(action).iter().any(|binding| match *binding {
+            Binding::Key(key) => self.keyboard_input.just_pressed(key),
+            Binding::Keys { negative, positive } => self.keyboard_input.any_just_pressed([negative, positive]),
+            Binding::Button(button) => self.button_just_pressed(button),
+            Binding::Buttons { negative, positive } => self.button_just_pressed(negative) || self.button_just_pressed(positive),
+            Binding::Axis { .. } => false,
+        })
+    }
+
+    fn binding_value(&self, binding: &Binding) -> f32 {
+        let sign = |negative: bool, positive: bool| positive as i8 as f32 - negative as i8 as f32;
+        match *binding {
+            Binding::Key(key) => sign(false, self.keyboard_input.pressed(key)),
+            Binding::Keys { negative, positive } => {
+                sign(self.keyboard_input.pressed(negative), self.keyboard_input.pressed(positive))
+            }
+            Binding::Button(button) => sign(false, self.button_pressed(button)),
+            Binding::Buttons { negative, positive } => sign(self.button_pressed(negative), self.button_pressed(positive)),
+            Binding::Axis { axis, inverted } => {
+                let value = self.gamepads
+                    .iter()
+                    .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                    .find(|value| value.abs() > AXIS_DEADZONE)
+                    .unwrap_or(0.0);
+                if inverted { -value } else { value }
+            }
+        }
+    }
+
+    fn button_pressed(&self, button_type: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type)))
+    }
+
+    fn button_just_pressed(&self, button_type: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    }
+}
+
+pub struct InputPlugin {
+    pub bindings_path: Option<PathBuf>,
+}
+
+impl Default for InputPlugin {
+    fn default() -> Self {
+        Self { bindings_path: Some(BINDINGS_PATH.into()) }
+    }
+}
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = self.bindings_path.as_deref().map_or_else(Bindings::default, Bindings::load);
+        app
+            .init_resource::<ShipInput>()
+            .insert_resource(bindings)
+            .insert_resource(BindingsPath(self.bindings_path.clone()))
+            .configure_sets(FixedUpdate, ShipInputSet.in_set(InGameSet::UserInput))
+            .add_systems(FixedUpdate, read_action_input.in_set(ShipInputSet))
+        ;
+    }
+}
+
+pub fn read_action_input(mut ship_input: ResMut<ShipInput>,
+                         action_input: ActionInput) {
+    *ship_input = ShipInput {
+        thrust: action_input.value(ShipAction::Thrust),
+        turn: action_input.value(ShipAction::Turn),
+        roll: action_input.value(ShipAction::Roll),
+        fire: action_input.pressed(ShipAction::Fire),
+        shield: action_input.pressed(ShipAction::Shield),
+        cycle_weapon: action_input.pressed(ShipAction::CycleWeapon),
+    };
+}
@@ -1,5 +1,7 @@
 mod movement;
 mod spaceship;
+// only added by hand while debugging
+#[allow(dead_code)]
 mod debug;
 mod camera;
 mod asteroids;
@@ -9,15 +11,50 @@ mod despawn;
 mod schedule;
 mod state;
 mod health;
+mod spatial_hash;
+mod cli;
+mod rng;
+mod input;
+mod replay;
+mod waves;
+mod shield;
+mod weapons;
+mod play_area;
+mod score;
+mod hud;
+mod menu;
+mod run_session;
+mod enemies;
+mod pickups;
+mod particles;
+mod model_pool;
+mod radar;
+#[cfg(test)]
+mod testing;
 
 use bevy::prelude::*;
 use crate::asset_loader::AssetLoaderPlugin;
 use crate::asteroids::AsteroidPlugin;
+use crate::enemies::EnemyPlugin;
+use crate::pickups::PickupPlugin;
+use crate::particles::ParticlePlugin;
 use crate::camera::CameraPlugin;
 use crate::collision_detection::CollisionDetectionPlugin;
-use crate::debug::DebugPlugin;
 use crate::despawn::DespawnPlugin;
+use crate::health::HealthPlugin;
 use crate::movement::{MovementPlugin};
+use crate::rng::RngPlugin;
+use crate::input::InputPlugin;
+use crate::replay::ReplayPlugin;
+use crate::waves::WavePlugin;
+use crate::shield::ShieldPlugin;
+use crate::weapons::WeaponsPlugin;
+use crate::play_area::PlayAreaPlugin;
+use crate::score::ScorePlugin;
+use crate::hud::HudPlugin;
+use crate::radar::RadarPlugin;
+use crate::menu::MenuPlugin;
+use crate::run_session::RunSessionPlugin;
 use crate::schedule::SchedulePlugin;
 use crate::spaceship::*;
 use crate::state::StatePlugin;
@@ -32,15 +69,31 @@ fn main() {
         })
         .add_plugins(DefaultPlugins)
         // custom plugins
+        .add_plugins(RunSessionPlugin)
+        .add_plugins(RngPlugin)
         .add_plugins(AssetLoaderPlugin)
         .add_plugins(SpaceshipPlugin)
+        .add_plugins(ShieldPlugin)
+        .add_plugins(WeaponsPlugin)
         .add_plugins(AsteroidPlugin)
+        .add_plugins(EnemyPlugin)
+        .add_plugins(PickupPlugin)
+        .add_plugins(ParticlePlugin)
+        .add_plugins(WavePlugin)
         .add_plugins(CameraPlugin)
         .add_plugins(MovementPlugin)
         .add_plugins(CollisionDetectionPlugin)
         .add_plugins(DespawnPlugin)
-        .add_plugins(SchedulePlugin)
+        .add_plugins(HealthPlugin)
+        .add_plugins(PlayAreaPlugin)
+        .add_plugins(ScorePlugin::default())
+        .add_plugins(HudPlugin)
+        .add_plugins(RadarPlugin)
+        .add_plugins(MenuPlugin)
+        .add_plugins(SchedulePlugin::default())
         .add_plugins(StatePlugin)
-        // .add_plugins(DebugPlugin)
+        .add_plugins(InputPlugin::default())
+        .add_plugins(ReplayPlugin)
+        // .add_plugins(debug::DebugPlugin)
         .run();
 }
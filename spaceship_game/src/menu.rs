@@ -0,0 +1,644 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use crate::input::{ActionInput, Binding, Bindings, BindingsPath, ShipAction};
+use crate::score::{HighScores, Score};
+use crate::run_session::RestartRun;
+use crate::state::GameState;
+
+const TITLE_FONT_SIZE: f32 = 64.0;
+const SUBTITLE_FONT_SIZE: f32 = 28.0;
+const BUTTON_FONT_SIZE: f32 = 32.0;
+const BUTTON_WIDTH: f32 = 360.0;
+const BUTTON_HEIGHT: f32 = 44.0;
+const ROW_GAP: f32 = 8.0;
+const OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const BUTTON_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.1);
+const SELECTED_BUTTON_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+
+/// Controls the settings screen can rebind, each to a key and a gamepad button: action, side of the action and label.
+const REBINDABLE_CONTROLS: [(ShipAction, f32, &str); 10] = [
+    (ShipAction::Thrust, 1.0, "Thrust"),
+    (ShipAction::Thrust, -1.0, "Reverse"),
+    (ShipAction::Turn, 1.0, "Turn left"),
+    (ShipAction::Turn, -1.0, "Turn right"),
+    (ShipAction::Roll, -1.0, "Roll left"),
+    (ShipAction::Roll, 1.0, "Roll right"),
+    (ShipAction::Fire, 1.0, "Fire"),
+    (ShipAction::Shield, 1.0, "Shield"),
+    (ShipAction::CycleWeapon, 1.0, "Cycle weapon"),
+    (ShipAction::Pause, 1.0, "Pause"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuAction {
+    Start,
+    Resume,
+    Restart,
+    Settings,
+    Back,
+    /// Waits for a key or button to bind to the entry of `REBINDABLE_CONTROLS` with this index.
+    Rebind(usize),
+    Quit,
+}
+
+impl MenuAction {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuAction::Start => "Start",
+            MenuAction::Resume => "Resume",
+            MenuAction::Restart => "Restart",
+            MenuAction::Settings => "Settings",
+            MenuAction::Back => "Back",
+            MenuAction::Rebind(index) => REBINDABLE_CONTROLS[*index].2,
+            MenuAction::Quit => "Quit",
+        }
+    }
+}
+
+/// Root of the screen shown for the current state, despawned when the state is left.
+#[derive(Component, Debug)]
+struct MenuScreen;
+
+#[derive(Component, Debug)]
+struct MenuButton {
+    index: usize,
+    action: MenuAction,
+}
+
+/// Index of the highlighted button on the current screen.
+#[derive(Resource, Debug, Default)]
+struct MenuSelection(usize);
+
+#[derive(Resource, Debug, Default)]
+struct SettingsScreen {
+    /// State the settings screen was opened from and goes back to.
+    return_state: GameState,
+    /// Entry of `REBINDABLE_CONTROLS` waiting for a key or button press.
+    rebinding: Option<usize>,
+    /// Why the last key or button pressed while rebinding wasn't bound.
+    refused: Option<String>,
+    /// Whether a key or button was rebound since the bindings were last saved.
+    bindings_changed: bool,
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MenuSelection>()
+            .init_resource::<SettingsScreen>()
+            .add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(GameState::Settings), spawn_settings_menu)
+            .add_systems(OnEnter(GameState::GameOver), spawn_game_over_screen)
+            .add_systems(OnExit(GameState::MainMenu), despawn_menu_screens)
+            .add_systems(OnExit(GameState::Paused), despawn_menu_screens)
+            .add_systems(OnExit(GameState::Settings), (despawn_menu_screens, save_bindings))
+            .add_systems(OnExit(GameState::GameOver), despawn_menu_screens)
+            .add_systems(
+                Update,
+                (
+                    capture_rebound_input.run_if(rebinding),
+                    (navigate_menu, highlight_selected_button, confirm_menu_selection.run_if(menu_confirm_pressed))
+                        .chain()
+                        .run_if(not(rebinding)),
+                )
+                    .chain()
+                    .run_if(not(in_state(GameState::InGame))),
+            )
+        ;
+    }
+}
+
+fn spawn_main_menu(mut commands: Commands, mut selection: ResMut<MenuSelection>) {
+    selection.0 = 0;
+    spawn_menu_screen(&mut commands, "Spaceship", None, &buttons(&[MenuAction::Start, MenuAction::Settings, MenuAction::Quit]));
+}
+
+fn spawn_pause_menu(mut commands: Commands, mut selection: ResMut<MenuSelection>) {
+    selection.0 = 0;
+    spawn_menu_screen(
+        &mut commands,
+        "Paused",
+        None,
+        &buttons(&[MenuAction::Resume, MenuAction::Restart, MenuAction::Settings, MenuAction::Quit]),
+    );
+}
+
+fn spawn_game_over_screen(mut commands: Commands,
+                          mut selection: ResMut<MenuSelection>,
+                          score: Res<Score>,
+                          high_scores: Res<HighScores>) {
+    selection.0 = 0;
+    let best = high_scores.scores.first().copied().unwrap_or_default();
+    let subtitle = format!("Final score: {}\nBest: {}", score.points, best);
+    spawn_menu_screen(&mut commands, "Game Over", Some(&subtitle), &buttons(&[MenuAction::Restart, MenuAction::Quit]));
+}
+
+fn spawn_settings_menu(mut commands: Commands, bindings: Res<Bindings>, settings: Res<SettingsScreen>) {
+    spawn_settings_screen(&mut commands, &bindings, &settings);
+}
+
+/// Lists every rebindable control with the key and gamepad button or axis it's bound to,
+/// `Back` leaves and saves the bindings.
+fn spawn_settings_screen(commands: &mut Commands, bindings: &Bindings, settings: &SettingsScreen) {
+    let mut buttons: Vec<(MenuAction, String)> = REBINDABLE_CONTROLS
+        .iter()
+        .enumerate()
+        .map(|(index, &(action, sign, label))| {
+            let bound = if settings.rebinding == Some(index) {
+                "...".to_string()
+            } else {
+                let key = bindings.key(action, sign).map_or("-".to_string(), |key| format!("{:?}", key));
+                let gamepad = match (bindings.button(action, sign), bindings.axis(action)) {
+                    (Some(button), _) => format!("{:?}", button),
+                    (None, Some(axis)) => format!("{:?}", axis),
+                    (None, None) => "-".to_string(),
+                };
+                format!("{} / {}", key, gamepad)
+            };
+            (MenuAction::Rebind(index), format!("{}: {}", label, bound))
+        })
+        .collect();
+    buttons.push((MenuAction::Back, MenuAction::Back.label().to_string()));
+
+    let subtitle = match (settings.rebinding, &settings.refused) {
+        (Some(_), Some(refused)) => refused.clone(),
+        (Some(_), None) => match bindings.key(ShipAction::MenuBack, 1.0) {
+            Some(key) => format!("Press a key or button to bind, {:?} cancels", key),
+            None => "Press a key or button to bind".to_string(),
+        },
+        (None, _) => "Select a control to rebind it".to_string(),
+    };
+    spawn_menu_screen(commands, "Controls", Some(&subtitle), &buttons);
+}
+
+fn buttons(actions: &[MenuAction]) -> Vec<(MenuAction, String)> {
+    actions.iter().map(|&action| (action, action.label().to_string())).collect()
+}
+
+fn spawn_menu_screen(commands: &mut Commands, title: &str, subtitle: Option<&str>, buttons: &[(MenuAction, String)]) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(ROW_GAP),
+                    ..default()
+                },
+                background_color: OVERLAY_COLOR.into(),
+                // draw over the HUD
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            MenuScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(text_bundle(title, TITLE_FONT_SIZE));
+            if let Some(subtitle) = subtitle {
+                parent.spawn(text_bundle(subtitle, SUBTITLE_FONT_SIZE).with_text_alignment(TextAlignment::Center));
+            }
+            for (index, (action, label)) in buttons.iter().enumerate() {
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(BUTTON_WIDTH),
+                                height: Val::Px(BUTTON_HEIGHT),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        MenuButton { index, action: *action },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(text_bundle(label, BUTTON_FONT_SIZE));
+                    });
+            }
+        });
+}
+
+fn text_bundle(value: &str, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
+
+fn despawn_menu_screens(mut commands: Commands, query: Query<Entity, With<MenuScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// `MenuUp` and `MenuDown` move the selection, wrapping around at either end.
+fn navigate_menu(mut selection: ResMut<MenuSelection>,
+                 button_query: Query<&MenuButton>,
+                 action_input: ActionInput) {
+    let button_count = button_query.iter().count();
+    if button_count == 0 {
+        return;
+    }
+    if action_input.just_pressed(ShipAction::MenuUp) {
+        selection.0 = (selection.0 + button_count - 1) % button_count;
+    }
+    if action_input.just_pressed(ShipAction::MenuDown) {
+        selection.0 = (selection.0 + 1) % button_count;
+    }
+}
+
+fn menu_confirm_pressed(action_input: ActionInput) -> bool {
+    action_input.just_pressed(ShipAction::MenuConfirm)
+}
+
+fn confirm_menu_selection(mut selection: ResMut<MenuSelection>,
+                          mut settings: ResMut<SettingsScreen>,
+                          mut next_state: ResMut<NextState<GameState>>,
+                          mut restart_writer: EventWriter<RestartRun>,
+                          mut exit_writer: EventWriter<AppExit>,
+                          state: Res<State<GameState>>,
+                          button_query: Query<&MenuButton>) {
+    let Some(button) = button_query.iter().find(|button| button.index == selection.0) else {
+        return;
+    };
+    match button.action {
+        MenuAction::Start | MenuAction::Resume => next_state.set(GameState::InGame),
+        MenuAction::Restart => {
+            restart_writer.send(RestartRun);
+            next_state.set(GameState::InGame);
+        }
+        MenuAction::Settings => {
+            settings.return_state = *state.get();
+            selection.0 = 0;
+            next_state.set(GameState::Settings);
+        }
+        MenuAction::Back => next_state.set(settings.return_state),
+        MenuAction::Rebind(index) => settings.rebinding = Some(index),
+        MenuAction::Quit => exit_writer.send(AppExit),
+    }
+}
+
+fn rebinding(settings: Res<SettingsScreen>) -> bool {
+    settings.rebinding.is_some()
+}
+
+/// Binds the first key or gamepad button pressed while rebinding, then redraws the settings screen with it.
+/// `MenuBack` cancels instead, and keys and buttons another control is bound to are refused.
+/// Whatever was pressed is consumed, so navigating and confirming don't also react to it this frame.
+fn capture_rebound_input(mut commands: Commands,
+                         mut settings: ResMut<SettingsScreen>,
+                         mut bindings: ResMut<Bindings>,
+                         mut keyboard_input: ResMut<Input<KeyCode>>,
+                         mut gamepad_input: ResMut<Input<GamepadButton>>,
+                         screen_query: Query<Entity, With<MenuScreen>>) {
+    let Some(index) = settings.rebinding else {
+        return;
+    };
+    let key = keyboard_input.get_just_pressed().next().copied();
+    let gamepad_button = gamepad_input.get_just_pressed().next().copied();
+    if let Some(key) = key {
+        keyboard_input.clear_just_pressed(key);
+    }
+    if let Some(gamepad_button) = gamepad_button {
+        gamepad_input.clear_just_pressed(gamepad_button);
+    }
+    let button = gamepad_button.map(|gamepad_button| gamepad_button.button_type);
+
+    let (action, sign, _) = REBINDABLE_CONTROLS[index];
+    let cancel = bindings.of(ShipAction::MenuBack);
+    let cancelled = key.is_some_and(|key| cancel.contains(&Binding::Key(key)))
+        || button.is_some_and(|button| cancel.contains(&Binding::Button(button)));
+    let refused = if cancelled {
+        None
+    } else if let Some(key) = key {
+        if let Some(other) = bound_elsewhere(index, |action, sign| bindings.key(action, sign) == Some(key)) {
+            Some(format!("{:?} is already bound to {}", key, other))
+        } else {
+            bindings.set_key(action, sign, key);
+            settings.bindings_changed = true;
+            None
+        }
+    } else if let Some(button) = button {
+        if let Some(other) = bound_elsewhere(index, |action, sign| bindings.button(action, sign) == Some(button)) {
+            Some(format!("{:?} is already bound to {}", button, other))
+        } else if bindings.set_button(action, sign, button) {
+            settings.bindings_changed = true;
+            None
+        } else {
+            Some("Bind the other side to a button first".to_string())
+        }
+    } else {
+        return;
+    };
+    if refused.is_none() {
+        settings.rebinding = None;
+    }
+    settings.refused = refused;
+
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_settings_screen(&mut commands, &bindings, &settings);
+}
+
+/// Label of another rebindable control for which `bound` holds.
+fn bound_elsewhere(index: usize, bound: impl Fn(ShipAction, f32) -> bool) -> Option<&'static str> {
+    REBINDABLE_CONTROLS
+        .iter()
+        .enumerate()
+        .find(|&(other, &(action, sign, _))| other != index && bound(action, sign))
+        .map(|(_, &(_, _, label))| label)
+}
+
+fn save_bindings(mut settings: ResMut<SettingsScreen>,
+                 bindings: Res<Bindings>,
+                 bindings_path: Res<BindingsPath>) {
+    if settings.bindings_changed {
+        if let Some(path) = &bindings_path.0 {
+            bindings.save(path);
+        }
+        settings.bindings_changed = false;
+    }
+}
+
+fn highlight_selected_button(selection: Res<MenuSelection>,
+                             mut button_query: Query<(&MenuButton, &mut BackgroundColor)>) {
+    for (button, mut background_color) in button_query.iter_mut() {
+        *background_color = if button.index == selection.0 {
+            SELECTED_BUTTON_COLOR.into()
+        } else {
+            BUTTON_COLOR.into()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+    use bevy::input::ButtonState;
+    use bevy::input::gamepad::{GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent, GamepadInfo};
+    use bevy::input::keyboard::KeyboardInput;
+    use crate::input::InputPlugin;
+    use crate::testing::{headless_app, EventCollector};
+    use super::*;
+
+    const GAMEPAD: Gamepad = Gamepad { id: 0 };
+
+    fn menu_app() -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins(MenuPlugin)
+            .init_resource::<Score>()
+            .init_resource::<HighScores>()
+        ;
+        app.update();
+        app
+    }
+
+    /// Presses and releases a key, one update each, like a real key press spread over frames.
+    fn tap(app: &mut App, key: KeyCode) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world.send_event(KeyboardInput { scan_code: 0, key_code: Some(key), state, window: Entity::PLACEHOLDER });
+            app.update();
+        }
+    }
+
+    fn connect_gamepad(app: &mut App) {
+        let info = GamepadInfo { name: "Test gamepad".to_string() };
+        app.world.send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(GAMEPAD, GamepadConnection::Connected(info))));
+        app.update();
+    }
+
+    fn tap_button(app: &mut App, button: GamepadButtonType) {
+        for value in [1.0, 0.0] {
+            app.world.send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(GAMEPAD, button, value)));
+            app.update();
+        }
+    }
+
+    fn state(app: &App) -> GameState {
+        *app.world.resource::<State<GameState>>().get()
+    }
+
+    fn selection(app: &App) -> usize {
+        app.world.resource::<MenuSelection>().0
+    }
+
+    /// Opens the settings from the main menu and starts rebinding thrust.
+    fn rebind_thrust(app: &mut App) {
+        tap(app, KeyCode::Down);
+        tap(app, KeyCode::Return);
+        assert_eq!(state(app), GameState::Settings);
+        tap(app, KeyCode::Return);
+        assert_eq!(app.world.resource::<SettingsScreen>().rebinding, Some(0));
+    }
+
+    fn settings(app: &App) -> &SettingsScreen {
+        app.world.resource::<SettingsScreen>()
+    }
+
+    #[test]
+    fn menu_keys_can_be_bound_without_triggering_the_menu() {
+        for key in [KeyCode::Return, KeyCode::Up, KeyCode::Down] {
+            let mut app = menu_app();
+            rebind_thrust(&mut app);
+            tap(&mut app, key);
+
+            assert_eq!(app.world.resource::<Bindings>().key(ShipAction::Thrust, 1.0), Some(key));
+            assert_eq!(app.world.resource::<SettingsScreen>().rebinding, None);
+            assert_eq!(state(&app), GameState::Settings);
+            assert_eq!(selection(&app), 0);
+        }
+    }
+
+    #[test]
+    fn the_menu_reacts_again_after_a_key_is_bound() {
+        let mut app = menu_app();
+        rebind_thrust(&mut app);
+        tap(&mut app, KeyCode::I);
+        tap(&mut app, KeyCode::Down);
+        assert_eq!(selection(&app), 1);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(app.world.resource::<SettingsScreen>().rebinding, Some(1));
+    }
+
+    #[test]
+    fn the_back_key_cancels_rebinding_instead_of_being_bound() {
+        let mut app = menu_app();
+        rebind_thrust(&mut app);
+        tap(&mut app, KeyCode::Escape);
+
+        assert_eq!(settings(&app).rebinding, None);
+        assert_eq!(app.world.resource::<Bindings>().key(ShipAction::Thrust, 1.0), Some(KeyCode::W));
+        assert!(!settings(&app).bindings_changed);
+        assert_eq!(state(&app), GameState::Settings);
+    }
+
+    #[test]
+    fn keys_bound_to_another_control_are_refused() {
+        let mut app = menu_app();
+        rebind_thrust(&mut app);
+        for key in [KeyCode::Space, KeyCode::S] {
+            tap(&mut app, key);
+            assert_eq!(settings(&app).rebinding, Some(0));
+            assert!(settings(&app).refused.is_some());
+        }
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.key(ShipAction::Thrust, 1.0), Some(KeyCode::W));
+        assert_eq!(bindings.key(ShipAction::Fire, 1.0), Some(KeyCode::Space));
+
+        tap(&mut app, KeyCode::I);
+        assert_eq!(settings(&app).rebinding, None);
+        assert_eq!(settings(&app).refused, None);
+        assert_eq!(app.world.resource::<Bindings>().key(ShipAction::Thrust, 1.0), Some(KeyCode::I));
+    }
+
+    #[test]
+    fn gamepad_buttons_can_be_rebound() {
+        let mut app = menu_app();
+        connect_gamepad(&mut app);
+        rebind_thrust(&mut app);
+        // cycling weapons already has it
+        tap_button(&mut app, GamepadButtonType::North);
+        assert_eq!(settings(&app).rebinding, Some(0));
+        tap_button(&mut app, GamepadButtonType::West);
+        assert_eq!(settings(&app).rebinding, None);
+
+        // then reverse, paired with it, and the stick still works too
+        tap_button(&mut app, GamepadButtonType::DPadDown);
+        tap_button(&mut app, GamepadButtonType::South);
+        tap_button(&mut app, GamepadButtonType::RightThumb);
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.button(ShipAction::Thrust, 1.0), Some(GamepadButtonType::West));
+        assert_eq!(bindings.button(ShipAction::Thrust, -1.0), Some(GamepadButtonType::RightThumb));
+        assert_eq!(bindings.axis(ShipAction::Thrust), Some(GamepadAxisType::LeftStickY));
+
+        // the gamepad's back button cancels
+        tap_button(&mut app, GamepadButtonType::South);
+        tap_button(&mut app, GamepadButtonType::East);
+        assert_eq!(settings(&app).rebinding, None);
+        assert_eq!(app.world.resource::<Bindings>().button(ShipAction::Thrust, -1.0), Some(GamepadButtonType::RightThumb));
+    }
+
+    #[test]
+    fn menus_are_navigated_through_the_bindings() {
+        let mut app = menu_app();
+        let mut bindings = app.world.resource_mut::<Bindings>();
+        bindings.set_key(ShipAction::MenuDown, 1.0, KeyCode::J);
+        bindings.set_key(ShipAction::MenuConfirm, 1.0, KeyCode::K);
+        tap(&mut app, KeyCode::J);
+        assert_eq!(selection(&app), 1);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::MainMenu);
+        tap(&mut app, KeyCode::K);
+        assert_eq!(state(&app), GameState::Settings);
+    }
+
+    #[test]
+    fn rebound_keys_are_saved_to_and_loaded_from_the_bindings_path() {
+        let path = env::temp_dir().join(format!("spaceship_game_bindings_{}.ron", process::id()));
+        let mut app = menu_app();
+        app.insert_resource(BindingsPath(Some(path.clone())));
+        rebind_thrust(&mut app);
+        tap(&mut app, KeyCode::I);
+        assert!(!path.exists());
+        tap(&mut app, KeyCode::Up);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::MainMenu);
+
+        let mut loaded = App::new();
+        loaded.add_plugins(InputPlugin { bindings_path: Some(path.clone()) });
+        let bindings = loaded.world.resource::<Bindings>();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bindings, app.world.resource::<Bindings>());
+        assert_eq!(bindings.key(ShipAction::Thrust, 1.0), Some(KeyCode::I));
+    }
+
+    #[test]
+    fn actions_missing_from_saved_bindings_keep_their_defaults() {
+        let path = env::temp_dir().join(format!("spaceship_game_old_bindings_{}.ron", process::id()));
+        fs::write(&path, "(actions: [(action: Fire, bindings: [Key(F)])])").unwrap();
+        let mut app = App::new();
+        app.add_plugins(InputPlugin { bindings_path: Some(path.clone()) });
+        fs::remove_file(&path).unwrap();
+
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.of(ShipAction::Fire), &[Binding::Key(KeyCode::F)]);
+        assert_eq!(bindings.of(ShipAction::MenuConfirm), Bindings::default().of(ShipAction::MenuConfirm));
+    }
+
+    #[test]
+    fn menus_move_between_states() {
+        let mut app = menu_app();
+        let mut restarts = EventCollector::<RestartRun>::default();
+        assert_eq!(state(&app), GameState::MainMenu);
+
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::InGame);
+
+        // pause toggles, resume is the first button
+        tap(&mut app, KeyCode::Escape);
+        assert_eq!(state(&app), GameState::Paused);
+        tap(&mut app, KeyCode::Escape);
+        assert_eq!(state(&app), GameState::InGame);
+        tap(&mut app, KeyCode::Escape);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::InGame);
+
+        // settings go back to where they were opened from, back is the last button
+        tap(&mut app, KeyCode::Escape);
+        tap(&mut app, KeyCode::Down);
+        tap(&mut app, KeyCode::Down);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::Settings);
+        tap(&mut app, KeyCode::Up);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::Paused);
+
+        assert!(restarts.read(&app).is_empty());
+        tap(&mut app, KeyCode::Down);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::InGame);
+        assert_eq!(restarts.read(&app).len(), 1);
+
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+        app.update();
+        assert_eq!(state(&app), GameState::GameOver);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(state(&app), GameState::InGame);
+        assert_eq!(restarts.read(&app).len(), 1);
+    }
+
+    #[test]
+    fn quit_exits_the_app() {
+        let mut app = menu_app();
+        let mut exits = EventCollector::<AppExit>::default();
+        tap(&mut app, KeyCode::Up);
+        tap(&mut app, KeyCode::Return);
+        assert_eq!(exits.read(&app).len(), 1);
+    }
+
+    #[test]
+    fn only_one_menu_screen_is_shown_at_a_time() {
+        let mut app = menu_app();
+        for key in [KeyCode::Return, KeyCode::Escape, KeyCode::Down, KeyCode::Down, KeyCode::Return] {
+            tap(&mut app, key);
+            let screens = app.world.query_filtered::<(), With<MenuScreen>>().iter(&app.world).count();
+            let expected = if state(&app) == GameState::InGame { 0 } else { 1 };
+            assert_eq!(screens, expected, "{:?}", state(&app));
+        }
+    }
+}
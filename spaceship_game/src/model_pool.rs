@@ -0,0 +1,427 @@
+use std::any::TypeId;
+use bevy::ecs::system::{Command, EntityCommands, SystemParam};
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+use bevy::utils::HashMap;
+use crate::asset_loader::SceneAssets;
+use crate::collision_detection::end_contacts;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    Asteroid,
+    Missile,
+    Spaceship,
+    Ufo,
+}
+
+/// The UFO borrows the spaceship's scene, reddened so it can't be mistaken for it.
+const UFO_TINT: Vec3 = Vec3::new(1.0, 0.3, 0.25);
+
+impl Model {
+    /// Whether entities with this model are pooled, only worth it for the ones that come and go all the time.
+    fn pooled(&self) -> bool {
+        matches!(self, Model::Asteroid | Model::Missile)
+    }
+
+    fn tint(&self) -> Option<Vec3> {
+        match self {
+            Model::Ufo => Some(UFO_TINT),
+            _ => None,
+        }
+    }
+}
+
+/// Multiplies the base color of every material in the scene spawned with it, see `tint_scenes`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Tint(pub Vec3);
+
+/// Entity with a pooled model. When it's despawned with `despawn_recycling` it keeps its
+/// model's scene instance but loses its gameplay components, and is hidden until handed out again.
+#[derive(Component, Debug)]
+pub struct PooledModel(Model);
+
+/// Marks a pooled entity that's hidden and waiting to be handed out again.
+#[derive(Component, Debug)]
+pub struct Pooled;
+
+/// Removes a bundle from a pooled entity.
+type Reset = fn(&mut EntityWorldMut);
+
+fn remove_bundle<B: Bundle>(entity: &mut EntityWorldMut) {
+    entity.remove::<B>();
+}
+
+/// Components a pooled entity keeps: its scene instance, where it is and whether it's shown.
+fn kept_while_pooled(type_id: TypeId) -> bool {
+    [
+        TypeId::of::<PooledModel>(),
+        TypeId::of::<Pooled>(),
+        TypeId::of::<Children>(),
+        TypeId::of::<Transform>(),
+        TypeId::of::<GlobalTransform>(),
+        TypeId::of::<Visibility>(),
+        TypeId::of::<InheritedVisibility>(),
+        TypeId::of::<ViewVisibility>(),
+    ]
+        .contains(&type_id)
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct ModelPool {
+    free: HashMap<Model, Vec<Entity>>,
+    /// Everything pooled entities were spawned with or given later, removed when they're returned to the pool.
+    resets: HashMap<TypeId, Reset>,
+    /// Entities with a model spawned so far, each one spawns a whole glTF hierarchy.
+    spawned: usize,
+}
+
+impl ModelPool {
+    pub fn spawned(&self) -> usize {
+        self.spawned
+    }
+
+    fn add_reset<B: Bundle>(&mut self) {
+        self.resets.entry(TypeId::of::<B>()).or_insert(remove_bundle::<B>);
+    }
+}
+
+pub trait ModelPoolAppExt {
+    /// Removes `T` from entities returned to the pool, for components inserted after they were spawned.
+    /// Whatever they were spawned with is removed anyway.
+    fn reset_when_pooled<T: Component>(&mut self) -> &mut Self;
+}
+
+impl ModelPoolAppExt for App {
+    fn reset_when_pooled<T: Component>(&mut self) -> &mut Self {
+        self.world.get_resource_or_insert_with(ModelPool::default).add_reset::<T>();
+        self
+    }
+}
+
+/// Scene assets together with the pool of entities already spawned with them.
+#[derive(SystemParam)]
+pub struct Models<'w> {
+    scene_assets: Res<'w, SceneAssets>,
+    pool: ResMut<'w, ModelPool>,
+}
+
+impl Models<'_> {
+    /// Spawns an entity with `bundle` and `model`, reusing a pooled one if there is any.
+    /// The bundle has to set everything the entity needs, as nothing of its previous use is left.
+    pub fn spawn<B: Bundle>(&mut self, commands: &mut Commands, model: Model, bundle: B) -> Entity {
+        if !model.pooled() {
+            return self.spawn_new(commands, model, bundle);
+        }
+        self.pool.add_reset::<B>();
+        if let Some(entity) = self.pool.free.get_mut(&model).and_then(Vec::pop) {
+            commands.entity(entity).remove::<Pooled>().insert(bundle);
+            return entity;
+        }
+        let entity = self.spawn_new(commands, model, bundle);
+        commands.entity(entity).insert(PooledModel(model));
+        entity
+    }
+
+    fn spawn_new(&mut self, commands: &mut Commands, model: Model, bundle: impl Bundle) -> Entity {
+        self.pool.spawned += 1;
+        let scene = match model {
+            Model::Asteroid => self.scene_assets.asteroid.clone(),
+            Model::Missile => self.scene_assets.missiles.clone(),
+            Model::Spaceship | Model::Ufo => self.scene_assets.spaceship.clone(),
+        };
+        commands
+            .spawn(bundle)
+            .with_children(|parent| {
+                let mut scene_root = parent.spawn(SceneBundle { scene, ..default() });
+                if let Some(tint) = model.tint() {
+                    scene_root.insert(Tint(tint));
+                }
+            })
+            .id()
+    }
+}
+
+/// Gives tinted scenes their own tinted copies of the materials they share with other instances.
+pub fn tint_scenes(mut ready: EventReader<SceneInstanceReady>,
+                   tint_query: Query<&Tint>,
+                   children_query: Query<&Children>,
+                   mut material_query: Query<&mut Handle<StandardMaterial>>,
+                   mut materials: ResMut<Assets<StandardMaterial>>) {
+    for event in ready.read() {
+        let Ok(&Tint(tint)) = tint_query.get(event.parent) else {
+            continue;
+        };
+        for descendant in children_query.iter_descendants(event.parent) {
+            let Ok(mut material) = material_query.get_mut(descendant) else {
+                continue;
+            };
+            let mut tinted = materials.get(material.id()).cloned().unwrap_or_default();
+            tinted.base_color *= tint;
+            *material = materials.add(tinted);
+        }
+    }
+}
+
+pub trait DespawnRecyclingExt {
+    /// Returns the entity to the `ModelPool` if its model is pooled, otherwise despawns it with its descendants.
+    fn despawn_recycling(self);
+}
+
+impl DespawnRecyclingExt for EntityCommands<'_, '_, '_> {
+    fn despawn_recycling(mut self) {
+        let entity = self.id();
+        self.commands().add(DespawnRecycling { entity });
+    }
+}
+
+struct DespawnRecycling {
+    entity: Entity,
+}
+
+impl Command for DespawnRecycling {
+    fn apply(self, world: &mut World) {
+        // the entity may already be gone or pooled, e.g. when it both expired and died this tick
+        let Some(entity) = world.get_entity(self.entity) else {
+            return;
+        };
+        if entity.contains::<Pooled>() {
+            return;
+        }
+        let Some(&PooledModel(model)) = entity.get::<PooledModel>() else {
+            world.entity_mut(self.entity).despawn_recursive();
+            return;
+        };
+        let transform = entity.get::<Transform>().copied().unwrap_or_default();
+
+        // whatever reuses the entity must not look like it was in contact all along
+        end_contacts(world, self.entity);
+
+        world.resource_scope(|world, mut pool: Mut<ModelPool>| {
+            let mut entity = world.entity_mut(self.entity);
+            for reset in pool.resets.values() {
+                reset(&mut entity);
+            }
+            let components = world.components();
+            let entity = world.entity(self.entity);
+            let leftover = || entity
+                .archetype()
+                .components()
+                .filter_map(|id| components.get_info(id))
+                .filter(|info| !info.type_id().is_some_and(kept_while_pooled));
+            if leftover().next().is_some() {
+                let names: Vec<&str> = leftover().map(|info| info.name()).collect();
+                warn!("Despawning {:?} instead of pooling it, nothing removes {:?} from it", self.entity, names);
+                world.entity_mut(self.entity).despawn_recursive();
+                return;
+            }
+            world.entity_mut(self.entity).insert((
+                Pooled,
+                SpatialBundle { visibility: Visibility::Hidden, ..SpatialBundle::from_transform(transform) },
+            ));
+            pool.free.entry(model).or_default().push(self.entity);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::scene::ScenePlugin;
+    use crate::collision_detection::{Collider, CollisionDetectionPlugin, CollisionStarted};
+    use crate::despawn::{DespawnPlugin, Lifetime};
+    use crate::health::{Health, HealthPlugin};
+    use crate::movement::Velocity;
+    use crate::testing::{count_allocations, headless_app, run_on_this_thread, run_ticks, start_game, EventCollector};
+    use crate::weapons::Homing;
+    use super::*;
+
+    const CHURN_TICKS: usize = 300;
+    const SPAWNS_PER_TICK: usize = 4;
+    const CHURN_LIFETIME_SECONDS: f32 = 0.5;
+    /// Entities in the stand-in for a model's glTF scene.
+    const SCENE_ENTITIES: usize = 8;
+
+    #[derive(Component, Debug)]
+    struct Unregistered;
+
+    fn pool_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((DespawnPlugin, HealthPlugin, CollisionDetectionPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    fn spawn(app: &mut App, model: Model, bundle: impl Bundle) -> Entity {
+        let mut bundle = Some(bundle);
+        app.world.run_system_once(move |mut commands: Commands, mut models: Models| {
+            models.spawn(&mut commands, model, bundle.take().unwrap())
+        })
+    }
+
+    fn recycle(app: &mut App, entity: Entity) {
+        app.world.run_system_once(move |mut commands: Commands| {
+            commands.entity(entity).despawn_recycling();
+            // despawning twice in one tick must not pool it twice
+            commands.entity(entity).despawn_recycling();
+        });
+    }
+
+    /// App whose models are a small scene that really gets spawned.
+    fn scene_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((TransformPlugin, HierarchyPlugin, ScenePlugin, DespawnPlugin, HealthPlugin));
+        let mut scene_world = World::new();
+        for _ in 0..SCENE_ENTITIES {
+            scene_world.spawn(TransformBundle::default());
+        }
+        let scene = app.world.resource_mut::<Assets<Scene>>().add(Scene::new(scene_world));
+        let mut scene_assets = app.world.resource_mut::<SceneAssets>();
+        scene_assets.missiles = scene.clone();
+        scene_assets.spaceship = scene;
+        start_game(&mut app);
+        app
+    }
+
+    struct Churn {
+        spawned: usize,
+        entities: usize,
+        allocations: usize,
+    }
+
+    /// Keeps spawning short-lived entities like a long fight does.
+    fn churn(model: Model, spawns_per_tick: usize) -> Churn {
+        let mut app = scene_app();
+        app.add_systems(FixedUpdate, move |mut commands: Commands, mut models: Models| {
+            for _ in 0..spawns_per_tick {
+                let bundle = (SpatialBundle::default(), Velocity::new(Vec3::X), Lifetime::new(CHURN_LIFETIME_SECONDS));
+                models.spawn(&mut commands, model, bundle);
+            }
+        });
+        run_on_this_thread(&mut app);
+        let allocations = count_allocations(|| run_ticks(&mut app, CHURN_TICKS));
+        Churn {
+            spawned: app.world.resource::<ModelPool>().spawned(),
+            entities: app.world.entities().len() as usize,
+            allocations,
+        }
+    }
+
+    #[test]
+    fn pooling_allocates_less_under_churn() {
+        let alive = SPAWNS_PER_TICK * ((CHURN_LIFETIME_SECONDS * 60.0) as usize + 2);
+        let idle = churn(Model::Missile, 0);
+        let pooled = churn(Model::Missile, SPAWNS_PER_TICK);
+        let unpooled = churn(Model::Spaceship, SPAWNS_PER_TICK);
+        // what running the schedule costs without any spawns
+        let pooled_allocations = pooled.allocations - idle.allocations;
+        let unpooled_allocations = unpooled.allocations - idle.allocations;
+        println!(
+            "{} spawns: pooled {} allocations, {} spawned; unpooled {} allocations, {} spawned",
+            CHURN_TICKS * SPAWNS_PER_TICK, pooled_allocations, pooled.spawned, unpooled_allocations, unpooled.spawned,
+        );
+
+        assert!(pooled.spawned <= alive, "{} spawned", pooled.spawned);
+        assert_eq!(unpooled.spawned, CHURN_TICKS * SPAWNS_PER_TICK);
+        // each spawned entity has a scene instance, pooled ones keep theirs
+        assert_eq!(pooled.entities, pooled.spawned * (SCENE_ENTITIES + 2));
+        assert!(unpooled.entities <= alive * (SCENE_ENTITIES + 2));
+        assert!(pooled_allocations * 2 < unpooled_allocations);
+    }
+
+    #[test]
+    fn reused_entities_start_over() {
+        let mut app = pool_app();
+        app.reset_when_pooled::<Homing>();
+        let first = spawn(&mut app, Model::Asteroid, (SpatialBundle::default(), Health::new(10.0), Velocity::new(Vec3::X)));
+        app.world.entity_mut(first).insert(Homing { turn_rate: 1.0 });
+        app.world.get_mut::<Health>(first).unwrap().current = 3.0;
+        recycle(&mut app, first);
+
+        assert!(app.world.get::<Pooled>(first).is_some());
+        assert!(app.world.get::<Health>(first).is_none());
+        assert!(app.world.get::<Homing>(first).is_none());
+        assert_eq!(app.world.get::<Visibility>(first), Some(&Visibility::Hidden));
+        assert_eq!(app.world.resource::<ModelPool>().free[&Model::Asteroid], vec![first]);
+
+        let second = spawn(&mut app, Model::Asteroid, (SpatialBundle::default(), Health::new(20.0), Velocity::new(Vec3::Z)));
+        assert_eq!(second, first);
+        assert!(app.world.get::<Pooled>(second).is_none());
+        assert_eq!(app.world.get::<Health>(second).unwrap().current, 20.0);
+        assert_eq!(app.world.get::<Velocity>(second).unwrap().value, Vec3::Z);
+        assert_eq!(app.world.get::<Visibility>(second), Some(&Visibility::Inherited));
+        assert_eq!(app.world.resource::<ModelPool>().spawned(), 1);
+    }
+
+    #[test]
+    fn entities_with_components_nothing_removes_are_despawned_instead() {
+        let mut app = pool_app();
+        let entity = spawn(&mut app, Model::Asteroid, SpatialBundle::default());
+        let children = app.world.get::<Children>(entity).unwrap().to_vec();
+        app.world.entity_mut(entity).insert(Unregistered);
+        recycle(&mut app, entity);
+
+        assert!(app.world.get_entity(entity).is_none());
+        assert!(children.iter().all(|&child| app.world.get_entity(child).is_none()));
+        assert!(app.world.resource::<ModelPool>().free.get(&Model::Asteroid).is_none_or(Vec::is_empty));
+    }
+
+    #[test]
+    fn unpooled_models_are_despawned() {
+        let mut app = pool_app();
+        let entity = spawn(&mut app, Model::Spaceship, SpatialBundle::default());
+        let children = app.world.get::<Children>(entity).unwrap().to_vec();
+        recycle(&mut app, entity);
+        assert!(app.world.get_entity(entity).is_none());
+        assert!(children.iter().all(|&child| app.world.get_entity(child).is_none()));
+    }
+
+    #[test]
+    fn ufos_get_tinted_copies_of_the_spaceship_materials() {
+        let mut app = headless_app();
+        app
+            .add_plugins((TransformPlugin, HierarchyPlugin, ScenePlugin))
+            .register_type::<Handle<StandardMaterial>>()
+            .add_systems(Update, tint_scenes);
+        let base_color = Color::rgb(0.8, 0.6, 0.4);
+        let material = app.world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial { base_color, ..default() });
+        let mut scene_world = World::new();
+        scene_world.spawn((TransformBundle::default(), material.clone()));
+        app.world.resource_mut::<SceneAssets>().spaceship = app.world.resource_mut::<Assets<Scene>>().add(Scene::new(scene_world));
+        start_game(&mut app);
+
+        let spaceship = spawn(&mut app, Model::Spaceship, SpatialBundle::default());
+        let ufo = spawn(&mut app, Model::Ufo, SpatialBundle::default());
+        run_ticks(&mut app, 3);
+
+        let mut materials_of = |entity: Entity| app.world.run_system_once(
+            move |children_query: Query<&Children>, material_query: Query<&Handle<StandardMaterial>>| {
+                material_query.iter_many(children_query.iter_descendants(entity)).cloned().collect::<Vec<_>>()
+            },
+        );
+        assert_eq!(materials_of(spaceship), vec![material.clone()]);
+        let ufo_materials = materials_of(ufo);
+        assert_eq!(ufo_materials.len(), 1);
+        assert_ne!(ufo_materials[0], material);
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(&material).unwrap().base_color, base_color);
+        assert_eq!(materials.get(&ufo_materials[0]).unwrap().base_color, base_color * UFO_TINT);
+    }
+
+    // like a fragment spawned right where its asteroid died, reusing the asteroid's entity
+    #[test]
+    fn contacts_with_a_reused_entity_start_over() {
+        let mut app = pool_app();
+        let collider = || (SpatialBundle::default(), Collider::new(1.0));
+        let ufo = app.world.spawn(collider()).id();
+        let asteroid = spawn(&mut app, Model::Asteroid, collider());
+        let mut started = EventCollector::<CollisionStarted>::default();
+        run_ticks(&mut app, 3);
+        assert!(started.read(&app).iter().any(|started| (started.entity, started.collided_entity) == (ufo, asteroid)));
+
+        recycle(&mut app, asteroid);
+        let fragment = spawn(&mut app, Model::Asteroid, collider());
+        assert_eq!(fragment, asteroid);
+        run_ticks(&mut app, 3);
+        assert!(started.read(&app).iter().any(|started| (started.entity, started.collided_entity) == (ufo, fragment)));
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use crate::collision_detection::Collider;
+use crate::model_pool::ModelPoolAppExt;
 use crate::schedule::InGameSet;
+use crate::state::GameState;
 
 #[derive(Component, Debug)]
 pub struct Velocity {
@@ -25,36 +27,185 @@ impl Acceleration {
     }
 }
 
+/// Simulation transforms before and after the last fixed tick.
+/// `Transform` is rendered interpolated between the two and restored to `current` before the next tick.
+#[derive(Component, Debug)]
+pub struct InterpolatedTransform {
+    pub previous: Transform,
+    pub current: Transform,
+}
+
+impl InterpolatedTransform {
+    pub fn new(transform: Transform) -> Self {
+        Self { previous: transform, current: transform }
+    }
+}
+
 #[derive(Bundle)]
 pub struct MovingObjectBundle {
     pub velocity: Velocity,
     pub acceleration: Acceleration,
     pub collider: Collider,
-    pub model: SceneBundle
+    /// Also shows entities handed out again by `Models`, they are hidden while pooled.
+    pub spatial: SpatialBundle,
 }
 
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (update_velocity, update_position)
-                .chain()
-                .in_set(InGameSet::EntityUpdates)
-        )
+        app
+            .reset_when_pooled::<InterpolatedTransform>()
+            .add_systems(
+                FixedUpdate,
+                (update_velocity, update_position)
+                    .chain()
+                    .in_set(InGameSet::EntityUpdates)
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    restore_simulation_transforms.before(InGameSet::DespawnEntities),
+                    (store_simulation_transforms, add_interpolated_transforms)
+                        .after(InGameSet::CollisionDetection),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(Update, interpolate_transforms.run_if(in_state(GameState::InGame)))
         ;
     }
 }
 
-fn update_position(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time>) {
+pub fn update_position(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time>) {
     for (vel, mut transform) in query.iter_mut() {
         transform.translation += vel.value * time.delta_seconds();
     }
 }
 
-fn update_velocity(mut query: Query<(&Acceleration, &mut Velocity)>, time: Res<Time>) {
+pub fn update_velocity(mut query: Query<(&Acceleration, &mut Velocity)>, time: Res<Time>) {
     for (acceleration, mut velocity) in query.iter_mut() {
         velocity.value += acceleration.value * time.delta_seconds();
     }
-}
\ No newline at end of file
+}
+
+fn restore_simulation_transforms(mut query: Query<(&mut Transform, &mut InterpolatedTransform)>) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        *transform = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+fn store_simulation_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.current = *transform;
+    }
+}
+
+fn add_interpolated_transforms(mut commands: Commands,
+                               query: Query<(Entity, &Transform), Added<Velocity>>) {
+    for (entity, transform) in query.iter() {
+        commands.entity(entity).insert(InterpolatedTransform::new(*transform));
+    }
+}
+
+pub fn interpolate_transforms(mut query: Query<(&mut Transform, &InterpolatedTransform)>,
+                          fixed_time: Res<Time<Fixed>>) {
+    let alpha = fixed_time.overstep_percentage();
+    for (mut transform, interpolated) in query.iter_mut() {
+        let InterpolatedTransform { previous, current } = interpolated;
+        transform.translation = previous.translation.lerp(current.translation, alpha);
+        transform.rotation = previous.rotation.slerp(current.rotation, alpha);
+        transform.scale = previous.scale.lerp(current.scale, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bevy::time::TimeUpdateStrategy;
+    use crate::testing::{headless_app, headless_app_with_tick_rate, run_ticks, start_game};
+    use super::*;
+
+    const VELOCITY: Vec3 = Vec3::new(6.0, 0.0, -3.0);
+    const ACCELERATION: Vec3 = Vec3::new(0.0, 0.0, 12.0);
+
+    fn movement_app(mut app: App) -> (App, Entity) {
+        app.add_plugins(MovementPlugin);
+        start_game(&mut app);
+        let entity = app.world.spawn((
+            TransformBundle::default(),
+            Velocity::new(VELOCITY),
+            Acceleration::new(ACCELERATION),
+        )).id();
+        (app, entity)
+    }
+
+    fn simulation_translation(app: &App, entity: Entity) -> Vec3 {
+        app.world.get::<InterpolatedTransform>(entity).unwrap().current.translation
+    }
+
+    #[test]
+    fn simulation_matches_across_tick_rates() {
+        let seconds = 2.0;
+        let expected = VELOCITY * seconds + ACCELERATION * seconds * seconds / 2.0;
+        for tick_rate_hz in [30.0, 60.0, 120.0, 240.0] {
+            let (mut app, entity) = movement_app(headless_app_with_tick_rate(tick_rate_hz));
+            run_ticks(&mut app, (seconds as f64 * tick_rate_hz) as usize);
+
+            // velocity is updated before position, so each tick moves a little further than the exact path
+            let translation = simulation_translation(&app, entity);
+            let error = ACCELERATION.length() * seconds / tick_rate_hz as f32;
+            assert!(translation.distance(expected) <= error * 0.51 + 1e-3, "{} Hz: {:?}", tick_rate_hz, translation);
+        }
+    }
+
+    #[test]
+    fn rendering_between_ticks_doesnt_change_the_simulation() {
+        let ticks = 90;
+        let (mut ticking, entity) = movement_app(headless_app());
+        run_ticks(&mut ticking, ticks);
+
+        // frames 0.7 ticks long, so nearly every frame renders part way into the next tick
+        let (mut rendering, rendered_entity) = movement_app(headless_app());
+        let frame = Duration::from_secs_f64(0.7 / 60.0);
+        rendering.insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+        while rendering.world.resource::<Time<Fixed>>().elapsed() < Duration::from_secs_f64(ticks as f64 / 60.0) {
+            rendering.update();
+        }
+        rendering.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        rendering.update();
+
+        assert_eq!(
+            simulation_translation(&rendering, rendered_entity),
+            simulation_translation(&ticking, entity),
+        );
+    }
+
+    #[test]
+    fn transforms_are_rendered_between_the_previous_and_current_tick() {
+        let (mut app, entity) = movement_app(headless_app());
+        run_ticks(&mut app, 10);
+        // half a tick per frame, rounded up to whole nanoseconds, so every other frame renders half way into the next tick
+        let timestep = app.world.resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration((timestep + Duration::from_nanos(1)) / 2));
+        let mut half_ticks = 0;
+        for _ in 0..6 {
+            app.update();
+            let overstep = app.world.resource::<Time<Fixed>>().overstep_percentage();
+            // a whole number of ticks, give or take a nanosecond
+            if overstep < 0.01 {
+                continue;
+            }
+            half_ticks += 1;
+            let InterpolatedTransform { previous, current } = *app.world.get::<InterpolatedTransform>(entity).unwrap();
+            let rendered = app.world.get::<Transform>(entity).unwrap().translation;
+
+            assert!((overstep - 0.5).abs() < 0.01, "{}", overstep);
+            assert!(rendered.abs_diff_eq(previous.translation.lerp(current.translation, overstep), 1e-5), "{:?}", rendered);
+            let from_previous = rendered - previous.translation;
+            let to_current = current.translation - rendered;
+            assert!(from_previous.dot(to_current) > 0.0, "{:?} isn't between {:?} and {:?}", rendered, previous, current);
+        }
+        assert_eq!(half_ticks, 3);
+    }
+}
@@ -0,0 +1,349 @@
+use std::f32::consts::TAU;
+use bevy::prelude::*;
+use rand::Rng;
+use crate::collision_detection::Collider;
+use crate::despawn::despawn_dead_entities;
+use crate::health::Died;
+use crate::movement::Velocity;
+use crate::run_session::{ResetRun, RunSessionSet};
+use crate::schedule::InGameSet;
+use crate::state::GameState;
+
+/// Particles alive at once, emitters skip particles past this.
+const MAX_PARTICLES: usize = 1024;
+const PARTICLE_SIZE: f32 = 0.4;
+const EXPLOSION_PARTICLES_PER_RADIUS: f32 = 8.0;
+const MAX_EXPLOSION_PARTICLES: usize = 48;
+/// Exhaust rate at full speed, it scales down linearly with the emitter's speed.
+const EXHAUST_RATE: f32 = 60.0;
+const EXHAUST_FULL_SPEED: f32 = 25.0;
+/// Distance behind the emitter's center the exhaust comes out at.
+const EXHAUST_OFFSET: f32 = 4.0;
+pub const TRAIL_RATE: f32 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleEffect {
+    Explosion,
+    Exhaust,
+    Trail,
+}
+
+impl ParticleEffect {
+    /// Seconds a particle of this effect lives.
+    fn lifetime(&self) -> f32 {
+        match self {
+            ParticleEffect::Explosion => 0.8,
+            ParticleEffect::Exhaust => 0.35,
+            ParticleEffect::Trail => 0.25,
+        }
+    }
+
+    fn speed(&self) -> f32 {
+        match self {
+            ParticleEffect::Explosion => 12.0,
+            ParticleEffect::Exhaust => 10.0,
+            ParticleEffect::Trail => 1.0,
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        match self {
+            ParticleEffect::Explosion => 1.5,
+            ParticleEffect::Exhaust => 1.0,
+            ParticleEffect::Trail => 0.6,
+        }
+    }
+}
+
+/// Emits `rate` particles per second at the entity's position.
+#[derive(Component, Debug)]
+pub struct ParticleEmitter {
+    pub effect: ParticleEffect,
+    pub rate: f32,
+    /// Fraction of a particle carried over to the next frame.
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: ParticleEffect, rate: f32) -> Self {
+        Self { effect, rate, accumulator: 0.0 }
+    }
+
+    /// Number of particles due after `delta_seconds`.
+    pub fn advance(&mut self, delta_seconds: f32) -> u32 {
+        self.accumulator += self.rate.max(0.0) * delta_seconds;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        count as u32
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Particle {
+    pub velocity: Vec3,
+    pub lifetime: Timer,
+    pub scale: f32,
+}
+
+impl Particle {
+    pub fn new(velocity: Vec3, lifetime: f32, scale: f32) -> Self {
+        Self { velocity, lifetime: Timer::from_seconds(lifetime, TimerMode::Once), scale }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.lifetime.finished()
+    }
+}
+
+#[derive(Resource, Debug)]
+struct ParticleAssets {
+    mesh: Handle<Mesh>,
+    explosion_material: Handle<StandardMaterial>,
+    exhaust_material: Handle<StandardMaterial>,
+    trail_material: Handle<StandardMaterial>,
+}
+
+impl ParticleAssets {
+    fn material(&self, effect: ParticleEffect) -> Handle<StandardMaterial> {
+        match effect {
+            ParticleEffect::Explosion => self.explosion_material.clone(),
+            ParticleEffect::Exhaust => self.exhaust_material.clone(),
+            ParticleEffect::Trail => self.trail_material.clone(),
+        }
+    }
+}
+
+/// Particle entities are never despawned, finished ones are hidden and handed out again.
+#[derive(Resource, Debug, Default)]
+struct ParticlePool {
+    free: Vec<Entity>,
+    spawned: usize,
+}
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ParticlePool>()
+            .add_systems(Startup, create_particle_assets)
+            // explosions read the dead entity's position before its despawn is applied
+            .add_systems(
+                FixedUpdate,
+                spawn_explosions
+                    .after(despawn_dead_entities)
+                    .in_set(InGameSet::DespawnEntities),
+            )
+            .add_systems(
+                Update,
+                (drive_exhaust_emitters, run_emitters, update_particles)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(ResetRun, release_all_particles.in_set(RunSessionSet::Cleanup))
+        ;
+    }
+}
+
+fn create_particle_assets(mut commands: Commands,
+                          mut meshes: ResMut<Assets<Mesh>>,
+                          mut materials: ResMut<Assets<StandardMaterial>>) {
+    let mut material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        emissive: color,
+        unlit: true,
+        ..default()
+    });
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: PARTICLE_SIZE })),
+        explosion_material: material(Color::rgb(1.0, 0.55, 0.1)),
+        exhaust_material: material(Color::rgb(0.4, 0.7, 1.0)),
+        trail_material: material(Color::rgb(0.9, 0.9, 0.9)),
+    });
+}
+
+/// Shows a particle, reusing a finished one if there is any.
+fn emit(commands: &mut Commands,
+        pool: &mut ParticlePool,
+        particle_assets: &ParticleAssets,
+        effect: ParticleEffect,
+        translation: Vec3,
+        velocity: Vec3) {
+    let entity = match pool.free.pop() {
+        Some(entity) => entity,
+        None if pool.spawned < MAX_PARTICLES => {
+            pool.spawned += 1;
+            commands.spawn_empty().id()
+        }
+        None => return,
+    };
+    commands.entity(entity).insert((
+        PbrBundle {
+            mesh: particle_assets.mesh.clone(),
+            material: particle_assets.material(effect),
+            transform: Transform::from_translation(translation).with_scale(Vec3::splat(effect.scale())),
+            ..default()
+        },
+        Particle::new(velocity, effect.lifetime(), effect.scale()),
+    ));
+}
+
+// particles are only visual, so they use their own randomness instead of `GameRng`
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    let angle = rng.gen_range(0.0..TAU);
+    Vec3::new(angle.cos(), 0.0, angle.sin())
+}
+
+fn spawn_explosions(mut commands: Commands,
+                    mut pool: ResMut<ParticlePool>,
+                    mut died_reader: EventReader<Died>,
+                    particle_assets: Res<ParticleAssets>,
+                    query: Query<(&Transform, &Collider)>) {
+    let mut rng = rand::thread_rng();
+    for died in died_reader.read() {
+        let Ok((transform, collider)) = query.get(died.entity) else {
+            continue;
+        };
+        let count = ((collider.radius * EXPLOSION_PARTICLES_PER_RADIUS) as usize).min(MAX_EXPLOSION_PARTICLES);
+        for _ in 0..count {
+            let speed = ParticleEffect::Explosion.speed() * rng.gen_range(0.3..1.0);
+            let velocity = random_direction(&mut rng) * speed;
+            emit(&mut commands, &mut pool, &particle_assets, ParticleEffect::Explosion, transform.translation, velocity);
+        }
+    }
+}
+
+fn drive_exhaust_emitters(mut query: Query<(&mut ParticleEmitter, &Velocity)>) {
+    for (mut emitter, velocity) in query.iter_mut() {
+        if emitter.effect == ParticleEffect::Exhaust {
+            emitter.rate = EXHAUST_RATE * (velocity.value.length() / EXHAUST_FULL_SPEED).min(1.0);
+        }
+    }
+}
+
+fn run_emitters(mut commands: Commands,
+                mut pool: ResMut<ParticlePool>,
+                particle_assets: Res<ParticleAssets>,
+                mut query: Query<(&Transform, &mut ParticleEmitter)>,
+                time: Res<Time>) {
+    let mut rng = rand::thread_rng();
+    for (transform, mut emitter) in query.iter_mut() {
+        let effect = emitter.effect;
+        for _ in 0..emitter.advance(time.delta_seconds()) {
+            let jitter = random_direction(&mut rng) * rng.gen_range(0.0..1.0);
+            // ships and missiles face along -forward, so exhaust and trails go out along +forward
+            let (translation, velocity) = match effect {
+                ParticleEffect::Exhaust => (
+                    transform.translation + transform.forward() * EXHAUST_OFFSET,
+                    (transform.forward() + jitter * 0.3) * effect.speed(),
+                ),
+                _ => (transform.translation, jitter * effect.speed()),
+            };
+            emit(&mut commands, &mut pool, &particle_assets, effect, translation, velocity);
+        }
+    }
+}
+
+/// Moves particles and shrinks them towards the end of their life, then hides them for reuse.
+fn update_particles(mut pool: ResMut<ParticlePool>,
+                    mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Visibility)>,
+                    time: Res<Time>) {
+    for (entity, mut particle, mut transform, mut visibility) in query.iter_mut() {
+        if !particle.is_active() {
+            continue;
+        }
+        particle.lifetime.tick(time.delta());
+        if !particle.is_active() {
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+            continue;
+        }
+        transform.translation += particle.velocity * time.delta_seconds();
+        transform.scale = Vec3::splat(particle.scale * (1.0 - particle.lifetime.percent()));
+    }
+}
+
+fn release_all_particles(mut pool: ResMut<ParticlePool>,
+                         mut query: Query<(Entity, &mut Particle, &mut Visibility)>) {
+    for (entity, mut particle, mut visibility) in query.iter_mut() {
+        if particle.is_active() {
+            let remaining = particle.lifetime.remaining();
+            particle.lifetime.tick(remaining);
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    const FRAME_SECONDS: f32 = 1.0 / 60.0;
+
+    fn particle_app() -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins(ParticlePlugin)
+            .add_event::<Died>()
+        ;
+        start_game(&mut app);
+        app
+    }
+
+    fn active_particles(app: &mut App) -> usize {
+        app.world.query::<&Particle>().iter(&app.world).filter(|particle| particle.is_active()).count()
+    }
+
+    #[test]
+    fn emitters_emit_their_rate_per_second() {
+        let mut emitter = ParticleEmitter::new(ParticleEffect::Trail, 45.0);
+        let emitted: u32 = (0..60).map(|_| emitter.advance(FRAME_SECONDS)).sum();
+        assert_eq!(emitted, 45);
+    }
+
+    #[test]
+    fn slow_emitters_carry_fractions_over() {
+        let mut emitter = ParticleEmitter::new(ParticleEffect::Trail, 10.0);
+        let emitted: Vec<u32> = (0..600).map(|_| emitter.advance(FRAME_SECONDS)).collect();
+        assert!(emitted.iter().all(|&count| count <= 1));
+        assert!(emitted.iter().sum::<u32>().abs_diff(100) <= 1);
+    }
+
+    #[test]
+    fn stopped_emitters_emit_nothing() {
+        let mut emitter = ParticleEmitter::new(ParticleEffect::Exhaust, 0.0);
+        assert_eq!(emitter.advance(1.0), 0);
+        emitter.rate = -5.0;
+        assert_eq!(emitter.advance(1.0), 0);
+    }
+
+    #[test]
+    fn particles_finish_after_their_lifetime_and_are_reused() {
+        let mut app = particle_app();
+        let effect = ParticleEffect::Trail;
+        let emitter = app.world.spawn((TransformBundle::default(), ParticleEmitter::new(effect, TRAIL_RATE))).id();
+
+        // only about a lifetime's worth of particles is alive at once, finished ones are handed out again
+        run_ticks(&mut app, 120);
+        let alive = (TRAIL_RATE * effect.lifetime()).ceil() as usize;
+        assert!(active_particles(&mut app).abs_diff(alive) <= 1);
+        assert!(app.world.resource::<ParticlePool>().spawned <= alive + 1);
+
+        app.world.despawn(emitter);
+        run_ticks(&mut app, (effect.lifetime() / FRAME_SECONDS) as usize + 1);
+        assert_eq!(active_particles(&mut app), 0);
+        let hidden = app.world.query::<&Visibility>().iter(&app.world).all(|visibility| visibility == Visibility::Hidden);
+        assert!(hidden);
+    }
+
+    #[test]
+    fn explosions_scale_with_the_collider() {
+        let mut app = particle_app();
+        let entity = app.world.spawn((TransformBundle::default(), Collider::new(2.0))).id();
+        app.world.send_event(Died { entity, killer: None });
+        run_ticks(&mut app, 1);
+        assert_eq!(active_particles(&mut app), (2.0 * EXPLOSION_PARTICLES_PER_RADIUS) as usize);
+    }
+}
@@ -0,0 +1,351 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use crate::asset_loader::RonAssetLoader;
+use crate::asteroids::{split_asteroids, AsteroidDestroyed};
+use crate::collision_detection::{apply_collision_damage, Collider, CollisionLayers, CollisionStarted};
+use crate::despawn::Lifetime;
+use crate::health::{Healed, Health};
+use crate::movement::{Acceleration, Velocity};
+use crate::rng::GameRng;
+use crate::schedule::InGameSet;
+use crate::shield::ShieldEnergy;
+use crate::spaceship::Spaceship;
+use crate::weapons::Weapon;
+
+const DROPS_PATH: &str = "default.drops.ron";
+const PICKUP_RADIUS: f32 = 1.5;
+const PICKUP_LIFETIME_SECONDS: f32 = 10.0;
+/// Share of the destroyed asteroid's velocity a pickup keeps drifting with.
+const PICKUP_DRIFT_SCALAR: f32 = 0.3;
+const REPAIR_AMOUNT: f32 = 30.0;
+const RAPID_FIRE_SECONDS: f32 = 8.0;
+const RAPID_FIRE_MULTIPLIER: f32 = 2.0;
+
+pub const PICKUP_LAYERS: CollisionLayers = CollisionLayers::new(CollisionLayers::PICKUP, CollisionLayers::SPACESHIP);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Pickup {
+    /// Restores `REPAIR_AMOUNT` health.
+    Repair,
+    /// Fills the shield energy up.
+    ShieldRefill,
+    /// Upgrades the equipped weapon.
+    WeaponUpgrade,
+    /// Weapons fire `RAPID_FIRE_MULTIPLIER` times as fast for `RAPID_FIRE_SECONDS`.
+    RapidFire,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropEntry {
+    pub pickup: Pickup,
+    /// Relative chance of this pickup being the one dropped.
+    pub weight: u32,
+}
+
+/// Pickups destroyed asteroids drop, loaded from a `.drops.ron` asset.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct DropTable {
+    /// Chance of a destroyed asteroid dropping anything at all.
+    pub chance: f64,
+    pub entries: Vec<DropEntry>,
+}
+
+impl DropTable {
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<Pickup> {
+        let total_weight: u32 = self.entries.iter().map(|entry| entry.weight).sum();
+        if total_weight == 0 || !rng.gen_bool(self.chance.clamp(0.0, 1.0)) {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total_weight);
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(entry.pickup);
+            }
+            roll -= entry.weight;
+        }
+        None
+    }
+}
+
+/// Temporary fire rate boost from a `Pickup::RapidFire`.
+#[derive(Component, Debug)]
+pub struct RapidFire {
+    pub timer: Timer,
+}
+
+#[derive(Resource, Debug)]
+pub struct PickupAssets {
+    drop_table: Handle<DropTable>,
+    mesh: Handle<Mesh>,
+    repair_material: Handle<StandardMaterial>,
+    shield_refill_material: Handle<StandardMaterial>,
+    weapon_upgrade_material: Handle<StandardMaterial>,
+    rapid_fire_material: Handle<StandardMaterial>,
+}
+
+impl PickupAssets {
+    fn material(&self, pickup: Pickup) -> Handle<StandardMaterial> {
+        match pickup {
+            Pickup::Repair => self.repair_material.clone(),
+            Pickup::ShieldRefill => self.shield_refill_material.clone(),
+            Pickup::WeaponUpgrade => self.weapon_upgrade_material.clone(),
+            Pickup::RapidFire => self.rapid_fire_material.clone(),
+        }
+    }
+}
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<DropTable>()
+            .register_asset_loader(RonAssetLoader::<DropTable>::new(&["drops.ron"]))
+            .add_systems(Startup, create_pickup_assets)
+            .add_systems(
+                FixedUpdate,
+                (
+                    drop_pickups.after(split_asteroids),
+                    collect_pickups.after(apply_collision_damage),
+                    update_rapid_fire,
+                )
+                    .in_set(InGameSet::EntityUpdates),
+            )
+        ;
+    }
+}
+
+fn create_pickup_assets(mut commands: Commands,
+                        asset_server: Res<AssetServer>,
+                        mut meshes: ResMut<Assets<Mesh>>,
+                        mut materials: ResMut<Assets<StandardMaterial>>) {
+    let mut material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        emissive: color,
+        ..default()
+    });
+    commands.insert_resource(PickupAssets {
+        drop_table: asset_server.load(DROPS_PATH),
+        mesh: meshes.add(Mesh::from(shape::UVSphere { radius: PICKUP_RADIUS, ..default() })),
+        repair_material: material(Color::rgb(0.2, 0.9, 0.3)),
+        shield_refill_material: material(Color::rgb(0.3, 0.6, 1.0)),
+        weapon_upgrade_material: material(Color::rgb(1.0, 0.7, 0.1)),
+        rapid_fire_material: material(Color::rgb(1.0, 0.2, 0.6)),
+    });
+}
+
+pub fn drop_pickups(mut commands: Commands,
+                mut destroyed_reader: EventReader<AsteroidDestroyed>,
+                mut rng: ResMut<GameRng>,
+                pickup_assets: Res<PickupAssets>,
+                drop_tables: Res<Assets<DropTable>>) {
+    let Some(drop_table) = drop_tables.get(&pickup_assets.drop_table) else {
+        return;
+    };
+    for destroyed in destroyed_reader.read() {
+        let Some(pickup) = drop_table.roll(&mut *rng) else {
+            continue;
+        };
+        commands.spawn((
+            PbrBundle {
+                mesh: pickup_assets.mesh.clone(),
+                material: pickup_assets.material(pickup),
+                transform: Transform::from_translation(destroyed.translation),
+                ..default()
+            },
+            pickup,
+            Velocity::new(destroyed.velocity * PICKUP_DRIFT_SCALAR),
+            Acceleration::new(Vec3::ZERO),
+            Collider::new(PICKUP_RADIUS),
+            PICKUP_LAYERS,
+            Lifetime::new(PICKUP_LIFETIME_SECONDS),
+        ));
+    }
+}
+
+fn collect_pickups(mut commands: Commands,
+                   mut started_reader: EventReader<CollisionStarted>,
+                   mut healed_writer: EventWriter<Healed>,
+                   pickup_query: Query<&Pickup>,
+                   mut spaceship_query: Query<(&mut Health, &mut ShieldEnergy, &mut Weapon), With<Spaceship>>) {
+    for &CollisionStarted { entity, collided_entity } in started_reader.read() {
+        let Ok(&pickup) = pickup_query.get(entity) else {
+            continue;
+        };
+        let Ok((mut health, mut energy, mut weapon)) = spaceship_query.get_mut(collided_entity) else {
+            continue;
+        };
+        match pickup {
+            Pickup::Repair => {
+                let amount = health.heal(REPAIR_AMOUNT);
+                if amount > 0.0 {
+                    healed_writer.send(Healed { entity: collided_entity, amount });
+                }
+            }
+            Pickup::ShieldRefill => {
+                energy.current = energy.max;
+                // a full shield can go up right away, even with the key still held since it ran out
+                energy.depleted = false;
+            }
+            Pickup::WeaponUpgrade => weapon.upgrade(),
+            Pickup::RapidFire => {
+                // picking up another one while active just restarts the timer
+                commands.entity(collided_entity).insert(RapidFire {
+                    timer: Timer::from_seconds(RAPID_FIRE_SECONDS, TimerMode::Once),
+                });
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// the weapon's own tick covers one share of the time, this adds the rest
+fn update_rapid_fire(mut commands: Commands,
+                     mut query: Query<(Entity, &mut RapidFire, &mut Weapon)>,
+                     time: Res<Time>) {
+    for (entity, mut rapid_fire, mut weapon) in query.iter_mut() {
+        weapon.tick(time.delta().mul_f32(RAPID_FIRE_MULTIPLIER - 1.0));
+        rapid_fire.timer.tick(time.delta());
+        if rapid_fire.timer.finished() {
+            commands.entity(entity).remove::<RapidFire>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::asteroids::{AsteroidPlugin, AsteroidSize};
+    use crate::collision_detection::CollisionDetectionPlugin;
+    use crate::health::HealthPlugin;
+    use crate::shield::SpaceshipShield;
+    use crate::spaceship::SpaceshipPlugin;
+    use crate::testing::{headless_app, run_ticks, start_game, EventCollector};
+    use super::*;
+
+    fn pickup_app() -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins((PickupPlugin, SpaceshipPlugin, AsteroidPlugin, HealthPlugin, CollisionDetectionPlugin))
+            .insert_resource(GameRng::from_seed(0))
+        ;
+        start_game(&mut app);
+        app
+    }
+
+    /// Drops the pickup onto the ship and ticks until it's collected.
+    fn collect(app: &mut App, pickup: Pickup) {
+        let translation = app.world.query_filtered::<&Transform, With<Spaceship>>().single(&app.world).translation;
+        let entity = app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            pickup,
+            Collider::new(PICKUP_RADIUS),
+            PICKUP_LAYERS,
+        )).id();
+        run_ticks(app, 2);
+        assert!(app.world.get_entity(entity).is_none());
+    }
+
+    fn spaceship<C: Component>(app: &mut App) -> &C {
+        app.world.query_filtered::<&C, With<Spaceship>>().single(&app.world)
+    }
+
+    fn spaceship_mut<C: Component>(app: &mut App) -> Mut<'_, C> {
+        app.world.query_filtered::<&mut C, With<Spaceship>>().single_mut(&mut app.world)
+    }
+
+    #[test]
+    fn repair_heals_the_ship() {
+        let mut app = pickup_app();
+        let mut healed = EventCollector::<Healed>::default();
+        spaceship_mut::<Health>(&mut app).current = 50.0;
+        collect(&mut app, Pickup::Repair);
+
+        assert!((spaceship::<Health>(&mut app).current - 50.0 - REPAIR_AMOUNT).abs() < 0.1);
+        let healed: Vec<f32> = healed.read(&app).iter().map(|healed| healed.amount).collect();
+        assert_eq!(healed, vec![REPAIR_AMOUNT]);
+    }
+
+    #[test]
+    fn repair_at_full_health_heals_nothing() {
+        let mut app = pickup_app();
+        let mut healed = EventCollector::<Healed>::default();
+        collect(&mut app, Pickup::Repair);
+        let health = spaceship::<Health>(&mut app);
+        assert_eq!(health.current, health.max);
+        assert!(healed.read(&app).is_empty());
+    }
+
+    #[test]
+    fn shield_refill_fills_the_shield_energy() {
+        let mut app = pickup_app();
+        // ran out while the shield key is still held
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::Tab);
+        let mut energy = spaceship_mut::<ShieldEnergy>(&mut app);
+        energy.current = 5.0;
+        energy.depleted = true;
+        collect(&mut app, Pickup::ShieldRefill);
+        let energy = spaceship::<ShieldEnergy>(&mut app);
+        assert_eq!(energy.current, energy.max);
+        assert!(!energy.depleted);
+
+        run_ticks(&mut app, 1);
+        assert!(app.world.query_filtered::<(), (With<Spaceship>, With<SpaceshipShield>)>().get_single(&app.world).is_ok());
+    }
+
+    #[test]
+    fn weapon_upgrade_raises_the_weapon_damage() {
+        let mut app = pickup_app();
+        let damage = spaceship::<Weapon>(&mut app).damage;
+        collect(&mut app, Pickup::WeaponUpgrade);
+        assert!(spaceship::<Weapon>(&mut app).damage > damage);
+    }
+
+    #[test]
+    fn rapid_fire_runs_out() {
+        let mut app = pickup_app();
+        collect(&mut app, Pickup::RapidFire);
+        assert!(app.world.query_filtered::<(), (With<RapidFire>, With<Spaceship>)>().get_single(&app.world).is_ok());
+
+        run_ticks(&mut app, (RAPID_FIRE_SECONDS * 60.0) as usize);
+        assert!(app.world.query_filtered::<(), With<RapidFire>>().get_single(&app.world).is_err());
+    }
+
+    #[test]
+    fn destroyed_asteroids_drop_rolled_pickups() {
+        let mut app = pickup_app();
+        let drop_table = app.world.resource_mut::<Assets<DropTable>>().add(DropTable {
+            chance: 1.0,
+            entries: vec![DropEntry { pickup: Pickup::ShieldRefill, weight: 1 }],
+        });
+        app.world.resource_mut::<PickupAssets>().drop_table = drop_table;
+        app.world.send_event(AsteroidDestroyed {
+            translation: Vec3::new(30.0, 0.0, 0.0),
+            velocity: Vec3::new(10.0, 0.0, 0.0),
+            size: AsteroidSize::Large,
+        });
+        run_ticks(&mut app, 1);
+
+        let (transform, &pickup, velocity) = app.world.query::<(&Transform, &Pickup, &Velocity)>().single(&app.world);
+        assert_eq!(pickup, Pickup::ShieldRefill);
+        assert_eq!(transform.translation, Vec3::new(30.0, 0.0, 0.0));
+        assert_eq!(velocity.value, Vec3::new(10.0, 0.0, 0.0) * PICKUP_DRIFT_SCALAR);
+    }
+
+    #[test]
+    fn rolls_respect_chance_and_weights() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let entries = vec![
+            DropEntry { pickup: Pickup::Repair, weight: 0 },
+            DropEntry { pickup: Pickup::RapidFire, weight: 1 },
+        ];
+        let never = DropTable { chance: 0.0, entries: entries.clone() };
+        let always = DropTable { chance: 1.0, entries };
+        for _ in 0..100 {
+            assert_eq!(never.roll(&mut rng), None);
+            assert_eq!(always.roll(&mut rng), Some(Pickup::RapidFire));
+        }
+    }
+}
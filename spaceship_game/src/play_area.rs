@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use crate::camera::{visible_half_extents, CAMERA_DISTANCE};
+use crate::cli;
+use crate::collision_detection::ContinuousCollision;
+use crate::movement::{update_position, InterpolatedTransform};
+use crate::schedule::InGameSet;
+
+const PLAY_AREA_ARG: &str = "play-area";
+const DESPAWN_DISTANCE: f32 = 100.0;
+/// Window shape the wrapping play area is sized for until the camera knows the real one.
+const DEFAULT_ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+/// Shape of the play area on the XZ plane, centered on the origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayAreaShape {
+    Circle { radius: f32 },
+    Rectangle { half_extents: Vec2 },
+}
+
+/// What happens to entities that leave the play area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Everything is despawned.
+    Despawn,
+    /// Entities with `Wraps` reappear at the opposite edge, everything else is despawned.
+    Wrap,
+}
+
+/// Selected with `--play-area despawn` (the default) or `--play-area wrap`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PlayArea {
+    pub shape: PlayAreaShape,
+    pub mode: BoundaryMode,
+}
+
+impl Default for PlayArea {
+    fn default() -> Self {
+        Self {
+            shape: PlayAreaShape::Circle { radius: DESPAWN_DISTANCE },
+            mode: BoundaryMode::Despawn,
+        }
+    }
+}
+
+impl PlayArea {
+    /// Asteroids-style play area matching the visible screen of a 16:9 window,
+    /// resized by the camera to the actual window.
+    pub fn wrapping() -> Self {
+        let projection = PerspectiveProjection { aspect_ratio: DEFAULT_ASPECT_RATIO, ..default() };
+        Self {
+            shape: PlayAreaShape::Rectangle { half_extents: visible_half_extents(&projection, CAMERA_DISTANCE) },
+            mode: BoundaryMode::Wrap,
+        }
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.mode == BoundaryMode::Wrap
+    }
+
+    pub fn contains(&self, translation: Vec3) -> bool {
+        let position = translation.xz();
+        match self.shape {
+            PlayAreaShape::Circle { radius } => position.length() <= radius,
+            PlayAreaShape::Rectangle { half_extents } => {
+                position.x.abs() <= half_extents.x && position.y.abs() <= half_extents.y
+            }
+        }
+    }
+
+    /// Where a translation outside of the play area reappears, `None` if it's inside.
+    pub fn wrap(&self, translation: Vec3) -> Option<Vec3> {
+        if self.contains(translation) {
+            return None;
+        }
+        match self.shape {
+            PlayAreaShape::Circle { radius } => {
+                let direction = Vec3::new(translation.x, 0.0, translation.z).normalize_or_zero();
+                Some(translation - direction * 2.0 * radius)
+            }
+            PlayAreaShape::Rectangle { half_extents } => {
+                let wrap_axis = |value: f32, half_extent: f32| {
+                    if value > half_extent {
+                        value - 2.0 * half_extent
+                    } else if value < -half_extent {
+                        value + 2.0 * half_extent
+                    } else {
+                        value
+                    }
+                };
+                Some(Vec3::new(
+                    wrap_axis(translation.x, half_extents.x),
+                    translation.y,
+                    wrap_axis(translation.z, half_extents.y),
+                ))
+            }
+        }
+    }
+
+    /// Copies of a sphere that sticks out over the edges, placed where the overhang shows up on the opposite side.
+    pub fn images(&self, translation: Vec3, radius: f32) -> impl Iterator<Item = Vec3> {
+        let offsets = match self.shape {
+            PlayAreaShape::Circle { radius: area_radius } => {
+                let overhangs = translation.xz().length() + radius > area_radius;
+                let direction = Vec3::new(translation.x, 0.0, translation.z).normalize_or_zero();
+                [overhangs.then_some(-direction * 2.0 * area_radius), None, None]
+            }
+            PlayAreaShape::Rectangle { half_extents } => {
+                let overhang = |value: f32, half_extent: f32| {
+                    if value + radius > half_extent {
+                        Some(-2.0 * half_extent)
+                    } else if value - radius < -half_extent {
+                        Some(2.0 * half_extent)
+                    } else {
+                        None
+                    }
+                };
+                let x = overhang(translation.x, half_extents.x);
+                let z = overhang(translation.z, half_extents.y);
+                [
+                    x.map(|x| Vec3::new(x, 0.0, 0.0)),
+                    z.map(|z| Vec3::new(0.0, 0.0, z)),
+                    x.zip(z).map(|(x, z)| Vec3::new(x, 0.0, z)),
+                ]
+            }
+        };
+        offsets.into_iter().flatten().map(move |offset| translation + offset)
+    }
+}
+
+/// Marks entities that wrap around the play area instead of being despawned when it's in `BoundaryMode::Wrap`.
+#[derive(Component, Debug)]
+pub struct Wraps;
+
+pub struct PlayAreaPlugin;
+
+impl Plugin for PlayAreaPlugin {
+    fn build(&self, app: &mut App) {
+        let play_area = match cli::arg_value(PLAY_AREA_ARG).as_deref() {
+            Some("wrap") => PlayArea::wrapping(),
+            Some("despawn") | None => PlayArea::default(),
+            Some(other) => {
+                warn!("Unknown play area mode {}, expected 'despawn' or 'wrap'", other);
+                PlayArea::default()
+            }
+        };
+
+        app
+            .insert_resource(play_area)
+            .add_systems(
+                FixedUpdate,
+                wrap_entities
+                    .after(update_position)
+                    .in_set(InGameSet::EntityUpdates)
+                    .run_if(|play_area: Res<PlayArea>| play_area.wraps()),
+            )
+        ;
+    }
+}
+
+fn wrap_entities(play_area: Res<PlayArea>,
+                 mut query: Query<(Entity, &mut Transform, Option<&mut InterpolatedTransform>), With<Wraps>>,
+                 mut continuous_query: Query<&mut ContinuousCollision>) {
+    for (entity, mut transform, interpolated) in query.iter_mut() {
+        let Some(wrapped) = play_area.wrap(transform.translation) else {
+            continue;
+        };
+        let offset = wrapped - transform.translation;
+        transform.translation = wrapped;
+        // move the history along too, so neither rendering nor swept collisions cross the whole screen
+        if let Some(mut interpolated) = interpolated {
+            interpolated.previous.translation += offset;
+        }
+        if let Ok(mut continuous) = continuous_query.get_mut(entity) {
+            if let Some(previous) = continuous.previous_translation.as_mut() {
+                *previous += offset;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half_extents(play_area: &PlayArea) -> Vec2 {
+        let PlayAreaShape::Rectangle { half_extents } = play_area.shape else {
+            panic!("{:?} isn't a rectangle", play_area.shape);
+        };
+        half_extents
+    }
+
+    #[test]
+    fn circle_contains_points_within_its_radius() {
+        let play_area = PlayArea::default();
+        assert!(play_area.contains(Vec3::ZERO));
+        assert!(play_area.contains(Vec3::new(DESPAWN_DISTANCE, 0.0, 0.0)));
+        assert!(play_area.contains(Vec3::new(-70.0, 0.0, 70.0)));
+        assert!(!play_area.contains(Vec3::new(-71.0, 0.0, 71.0)));
+        assert!(!play_area.contains(Vec3::new(0.0, 0.0, -DESPAWN_DISTANCE - 0.1)));
+    }
+
+    #[test]
+    fn rectangle_contains_points_within_its_half_extents() {
+        let play_area = PlayArea::wrapping();
+        let half_extents = half_extents(&play_area);
+        let corner = Vec3::new(half_extents.x, 0.0, half_extents.y);
+        assert!(play_area.contains(Vec3::ZERO));
+        assert!(play_area.contains(corner));
+        assert!(play_area.contains(-corner));
+        assert!(!play_area.contains(corner + Vec3::X * 0.1));
+        assert!(!play_area.contains(-corner - Vec3::Z * 0.1));
+    }
+
+    #[test]
+    fn height_is_ignored() {
+        assert!(PlayArea::default().contains(Vec3::new(0.0, 500.0, 0.0)));
+        assert!(PlayArea::wrapping().contains(Vec3::new(0.0, -500.0, 0.0)));
+    }
+
+    #[test]
+    fn points_outside_wrap_to_the_opposite_edge() {
+        let play_area = PlayArea::wrapping();
+        let edge = half_extents(&play_area).x;
+        assert_eq!(play_area.wrap(Vec3::new(10.0, 0.0, 0.0)), None);
+        let wrapped = play_area.wrap(Vec3::new(edge + 1.0, 0.0, 5.0)).unwrap();
+        assert_eq!(wrapped, Vec3::new(-edge + 1.0, 0.0, 5.0));
+        assert!(play_area.contains(wrapped));
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use crate::enemies::Ufo;
+use crate::hud::Hud;
+use crate::spaceship::Spaceship;
+use crate::waves::Hostile;
+
+const RADAR_SIZE: f32 = 160.0;
+const RADAR_MARGIN: f32 = 16.0;
+const RADAR_RANGE: f32 = 80.0;
+const BLIP_SIZE: f32 = 6.0;
+/// Threats out of range are shown smaller, pinned to the edge in their direction.
+const EDGE_INDICATOR_SIZE: f32 = 4.0;
+const RADAR_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.3, 0.1, 0.35);
+const SPACESHIP_BLIP_COLOR: Color = Color::WHITE;
+const ASTEROID_BLIP_COLOR: Color = Color::rgb(0.75, 0.75, 0.75);
+const UFO_BLIP_COLOR: Color = Color::rgb(1.0, 0.25, 0.25);
+const EDGE_INDICATOR_ALPHA: f32 = 0.6;
+
+/// World distance from the spaceship covered by the radar, from its center to its edge.
+#[derive(Resource, Debug)]
+pub struct RadarSettings {
+    pub range: f32,
+}
+
+impl Default for RadarSettings {
+    fn default() -> Self {
+        Self { range: RADAR_RANGE }
+    }
+}
+
+#[derive(Component, Debug)]
+struct RadarPanel;
+
+/// Blips are reused from frame to frame, ones not needed are hidden.
+#[derive(Component, Debug)]
+struct RadarBlip;
+
+pub struct RadarPlugin;
+
+impl Plugin for RadarPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RadarSettings>()
+            .add_systems(Startup, spawn_radar)
+            .add_systems(Update, update_radar)
+        ;
+    }
+}
+
+fn spawn_radar(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(RADAR_MARGIN),
+                    right: Val::Px(RADAR_MARGIN),
+                    width: Val::Px(RADAR_SIZE),
+                    height: Val::Px(RADAR_SIZE),
+                    ..default()
+                },
+                background_color: RADAR_BACKGROUND_COLOR.into(),
+                ..default()
+            },
+            RadarPanel,
+            // shown and hidden along with the rest of the HUD
+            Hud,
+        ))
+        .with_children(|parent| {
+            parent.spawn(blip_bundle(Vec2::ZERO, BLIP_SIZE, SPACESHIP_BLIP_COLOR));
+        });
+}
+
+/// `position` is relative to the radar's center, in pixels.
+fn blip_bundle(position: Vec2, size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: blip_style(position, size),
+        background_color: color.into(),
+        ..default()
+    }
+}
+
+fn blip_style(position: Vec2, size: f32) -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        left: Val::Px(RADAR_SIZE / 2.0 + position.x - size / 2.0),
+        top: Val::Px(RADAR_SIZE / 2.0 + position.y - size / 2.0),
+        width: Val::Px(size),
+        height: Val::Px(size),
+        ..default()
+    }
+}
+
+/// Position of a threat on the radar in pixels from its center, and whether it's out of range.
+/// The camera looks down with +Z up, which puts world +X on the left of the screen.
+fn radar_position(offset: Vec3, range: f32) -> (Vec2, bool) {
+    let position = Vec2::new(-offset.x, -offset.z) / range;
+    let out_of_range = position.length() > 1.0;
+    let position = if out_of_range { position.normalize() } else { position };
+    (position * RADAR_SIZE / 2.0, out_of_range)
+}
+
+fn update_radar(mut commands: Commands,
+                settings: Res<RadarSettings>,
+                panel_query: Query<Entity, With<RadarPanel>>,
+                mut blip_query: Query<(&mut Style, &mut BackgroundColor, &mut Visibility), With<RadarBlip>>,
+                spaceship_query: Query<&Transform, With<Spaceship>>,
+                threat_query: Query<(&Transform, Has<Ufo>), With<Hostile>>) {
+    let Ok(panel) = panel_query.get_single() else {
+        return;
+    };
+    let center = spaceship_query.get_single().map_or(Vec3::ZERO, |transform| transform.translation);
+
+    let mut blips = blip_query.iter_mut();
+    for (transform, is_ufo) in threat_query.iter() {
+        let (position, out_of_range) = radar_position(transform.translation - center, settings.range);
+        let color = if is_ufo { UFO_BLIP_COLOR } else { ASTEROID_BLIP_COLOR };
+        let (size, color) = if out_of_range {
+            (EDGE_INDICATOR_SIZE, color.with_a(EDGE_INDICATOR_ALPHA))
+        } else {
+            (BLIP_SIZE, color)
+        };
+
+        match blips.next() {
+            Some((mut style, mut background_color, mut visibility)) => {
+                *style = blip_style(position, size);
+                *background_color = color.into();
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                let blip = commands.spawn((blip_bundle(position, size, color), RadarBlip)).id();
+                commands.entity(panel).add_child(blip);
+            }
+        }
+    }
+    for (_, _, mut visibility) in blips {
+        *visibility = Visibility::Hidden;
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::cli;
+use crate::input::{read_action_input, ShipInput, ShipInputSet};
+use crate::rng::GameRng;
+use crate::run_session::{ResetRun, RunSessionSet};
+
+const RECORD_ARG: &str = "record";
+const REPLAY_ARG: &str = "replay";
+
+/// Everything needed to play a run back: the seed, the tick rate and the input of every tick.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub seed: u64,
+    pub tick_rate_hz: f64,
+    pub ticks: Vec<ShipInput>,
+}
+
+#[derive(Resource, Debug)]
+pub struct InputRecorder {
+    path: String,
+    recording: InputRecording,
+}
+
+#[derive(Resource, Debug)]
+pub struct InputReplay {
+    recording: InputRecording,
+    tick: usize,
+}
+
+/// `--record <file>` writes the input of every tick to a RON file on exit,
+/// `--replay <file>` feeds a recorded file back instead of the keyboard.
+/// A restart starts both over, so the file holds the run since the last restart,
+/// which plays out from a fresh start exactly like it did after the restart.
+///
+/// Has to be added after `RngPlugin` and `SchedulePlugin`, as a replay overrides their seed and tick rate.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = cli::arg_value(REPLAY_ARG) {
+            match load_recording(&path) {
+                Ok(recording) => {
+                    info!("Replaying {} ticks from {}", recording.ticks.len(), path);
+                    app
+                        .insert_resource(GameRng::from_seed(recording.seed))
+                        .insert_resource(Time::<Fixed>::from_hz(recording.tick_rate_hz))
+                        .insert_resource(InputReplay { recording, tick: 0 });
+                }
+                Err(err) => error!("Failed to load replay {}: {}", path, err),
+            }
+        }
+
+        if let Some(path) = cli::arg_value(RECORD_ARG) {
+            let seed = app.world.resource::<GameRng>().seed();
+            let tick_rate_hz = 1.0 / app.world.resource::<Time<Fixed>>().timestep().as_secs_f64();
+            app.insert_resource(InputRecorder {
+                path,
+                recording: InputRecording { seed, tick_rate_hz, ticks: vec![] },
+            });
+        }
+
+        app
+            .add_systems(
+                FixedUpdate,
+                (
+                    replay_input.run_if(resource_exists::<InputReplay>()),
+                    record_input.run_if(resource_exists::<InputRecorder>()),
+                )
+                    .chain()
+                    .after(read_action_input)
+                    .in_set(ShipInputSet),
+            )
+            .add_systems(
+                ResetRun,
+                (
+                    rewind_replay.run_if(resource_exists::<InputReplay>()),
+                    restart_recording.run_if(resource_exists::<InputRecorder>()),
+                )
+                    .in_set(RunSessionSet::Reset),
+            )
+            .add_systems(Last, save_recording.run_if(resource_exists::<InputRecorder>()))
+        ;
+    }
+}
+
+fn load_recording(path: &str) -> Result<InputRecording, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::from_str(&contents).map_err(|err| err.to_string())
+}
+
+fn replay_input(mut ship_input: ResMut<ShipInput>,
+                mut replay: ResMut<InputReplay>) {
+    let tick = replay.tick;
+    *ship_input = match replay.recording.ticks.get(tick) {
+        Some(&input) => input,
+        None => {
+            if tick == replay.recording.ticks.len() {
+                info!("Replay finished after {} ticks", tick);
+            }
+            ShipInput::default()
+        }
+    };
+    replay.tick += 1;
+}
+
+fn record_input(ship_input: Res<ShipInput>,
+                mut recorder: ResMut<InputRecorder>) {
+    recorder.recording.ticks.push(*ship_input);
+}
+
+fn rewind_replay(mut replay: ResMut<InputReplay>) {
+    replay.tick = 0;
+}
+
+fn restart_recording(mut recorder: ResMut<InputRecorder>) {
+    recorder.recording.ticks.clear();
+}
+
+fn save_recording(mut exit_reader: EventReader<AppExit>,
+                  recorder: Res<InputRecorder>) {
+    if exit_reader.read().last().is_none() {
+        return;
+    }
+    let result = ron::to_string(&recorder.recording)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| fs::write(&recorder.path, contents).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => info!("Saved {} ticks of input to {}", recorder.recording.ticks.len(), recorder.path),
+        Err(err) => error!("Failed to save recording to {}: {}", recorder.path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_session::RestartRun;
+    use crate::testing::{game_app, run_ticks, world_snapshot};
+    use super::*;
+
+    const SEED: u64 = 3;
+    const TICKS: usize = 300;
+
+    /// Steers, thrusts and fires in bursts, so the run depends on the input of many ticks.
+    fn pressed_keys(tick: usize) -> Vec<KeyCode> {
+        let mut keys = vec![];
+        if tick % 90 < 40 {
+            keys.push(KeyCode::W);
+        }
+        if tick % 70 >= 50 {
+            keys.push(KeyCode::A);
+        }
+        if tick % 20 < 5 {
+            keys.push(KeyCode::Space);
+        }
+        keys
+    }
+
+    fn recording_app() -> App {
+        let mut app = game_app(SEED);
+        app.insert_resource(InputRecorder {
+            path: String::new(),
+            recording: InputRecording { seed: SEED, tick_rate_hz: 60.0, ticks: vec![] },
+        });
+        app
+    }
+
+    fn play(app: &mut App) {
+        for tick in 0..TICKS {
+            let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+            keyboard_input.release_all();
+            for key in pressed_keys(tick) {
+                keyboard_input.press(key);
+            }
+            run_ticks(app, 1);
+        }
+        app.world.resource_mut::<Input<KeyCode>>().release_all();
+    }
+
+    fn replay(recording: InputRecording) -> Vec<String> {
+        let mut app = game_app(recording.seed);
+        app.insert_resource(InputReplay { recording, tick: 0 });
+        run_ticks(&mut app, TICKS);
+        world_snapshot(&mut app)
+    }
+
+    #[test]
+    fn replaying_a_recording_reaches_the_same_world() {
+        let mut app = recording_app();
+        play(&mut app);
+        let recorded = world_snapshot(&mut app);
+        let recording = app.world.remove_resource::<InputRecorder>().unwrap().recording;
+        assert_eq!(recording.ticks.len(), TICKS);
+        assert!(recording.ticks.iter().any(|input| input.fire));
+        assert_eq!(replay(recording), recorded);
+
+        // without the recording the ship just sits there
+        let mut idle_app = game_app(SEED);
+        run_ticks(&mut idle_app, TICKS);
+        assert_ne!(world_snapshot(&mut idle_app), recorded);
+    }
+
+    #[test]
+    fn a_restart_starts_a_new_recording() {
+        let mut app = recording_app();
+        run_ticks(&mut app, 100);
+        app.world.send_event(RestartRun);
+        app.update();
+        assert!(app.world.resource::<InputRecorder>().recording.ticks.is_empty());
+
+        play(&mut app);
+        let recorded = world_snapshot(&mut app);
+        let recording = app.world.remove_resource::<InputRecorder>().unwrap().recording;
+        assert_eq!(recording.ticks.len(), TICKS);
+        assert_eq!(replay(recording), recorded);
+    }
+
+    #[test]
+    fn a_restart_rewinds_the_replay() {
+        let mut app = game_app(SEED);
+        app.insert_resource(InputReplay { recording: InputRecording::default(), tick: 0 });
+        run_ticks(&mut app, 100);
+        app.world.send_event(RestartRun);
+        app.update();
+        assert_eq!(app.world.resource::<InputReplay>().tick, 0);
+    }
+}
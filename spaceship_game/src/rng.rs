@@ -0,0 +1,110 @@
+use std::env;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use crate::cli;
+use crate::run_session::{ResetRun, RunSessionSet};
+
+const SEED_ARG: &str = "seed";
+const SEED_ENV_VAR: &str = "SPACESHIP_SEED";
+
+/// Source of all gameplay randomness, so the same seed and inputs always play out the same.
+#[derive(Resource, Debug)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = seed_from_args();
+        info!("Game seed: {}", seed);
+        app
+            .insert_resource(GameRng::from_seed(seed))
+            .add_systems(ResetRun, reseed_rng.in_set(RunSessionSet::Reset))
+        ;
+    }
+}
+
+/// Reads the seed from `--seed` or the `SPACESHIP_SEED` environment variable, falling back to a random one.
+fn seed_from_args() -> u64 {
+    let value = cli::arg_value(SEED_ARG).or_else(|| env::var(SEED_ENV_VAR).ok());
+    match value.map(|value| value.parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(err)) => {
+            warn!("Invalid seed, using a random one instead: {}", err);
+            rand::random()
+        }
+        None => rand::random(),
+    }
+}
+
+// every run replays the same random sequence, so a restart plays out like the first run did
+fn reseed_rng(mut rng: ResMut<GameRng>) {
+    *rng = GameRng::from_seed(rng.seed());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asteroids::Asteroid;
+    use crate::movement::{Acceleration, Velocity};
+    use crate::testing::{game_app, run_ticks};
+    use super::*;
+
+    const TICKS: usize = 600;
+
+    /// Translation, velocity and acceleration of every asteroid after every tick.
+    fn asteroid_history(seed: u64) -> Vec<Vec<[Vec3; 3]>> {
+        let mut app = game_app(seed);
+        let mut history = vec![];
+        for _ in 0..TICKS {
+            run_ticks(&mut app, 1);
+            let mut asteroids: Vec<[Vec3; 3]> = app.world
+                .query_filtered::<(&Transform, &Velocity, &Acceleration), With<Asteroid>>()
+                .iter(&app.world)
+                .map(|(transform, velocity, acceleration)| [transform.translation, velocity.value, acceleration.value])
+                .collect();
+            asteroids.sort_by(|a, b| a[0].x.total_cmp(&b[0].x).then(a[0].z.total_cmp(&b[0].z)));
+            history.push(asteroids);
+        }
+        history
+    }
+
+    #[test]
+    fn the_same_seed_spawns_the_same_asteroids() {
+        let history = asteroid_history(7);
+        assert!(history.last().is_some_and(|asteroids| asteroids.len() > 1));
+        assert_eq!(history, asteroid_history(7));
+        assert_ne!(history, asteroid_history(8));
+    }
+}
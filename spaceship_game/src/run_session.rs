@@ -0,0 +1,90 @@
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+/// Builds the world for a new run, on startup and whenever `RestartRun` is sent.
+///
+/// Plugins register reset hooks here instead of spawning or resetting on their own,
+/// so a restarted run starts from exactly the same world as the first one.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResetRun;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RunSessionSet {
+    /// Despawn whatever the previous run left behind.
+    Cleanup,
+    /// Put gameplay resources back to their initial values.
+    Reset,
+    /// Spawn the initial entities.
+    Spawn,
+}
+
+/// Sent when the player starts a new run from the pause or game over screen.
+#[derive(Event, Debug)]
+pub struct RestartRun;
+
+pub struct RunSessionPlugin;
+
+impl Plugin for RunSessionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<RestartRun>()
+            .add_schedule(Schedule::new(ResetRun))
+            .configure_sets(
+                ResetRun,
+                (RunSessionSet::Cleanup, RunSessionSet::Reset, RunSessionSet::Spawn).chain(),
+            )
+            .add_systems(PostStartup, run_reset_schedule)
+            .add_systems(PostUpdate, run_reset_schedule.run_if(on_event::<RestartRun>()))
+        ;
+    }
+}
+
+fn run_reset_schedule(world: &mut World) {
+    world.run_schedule(ResetRun);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{game_app, run_ticks, world_snapshot};
+    use crate::waves::WaveDirector;
+    use super::*;
+
+    const SEED: u64 = 5;
+
+    /// The gameplay entities and score, plus the wave the director is on.
+    fn snapshot(app: &mut App) -> Vec<String> {
+        let mut snapshot = world_snapshot(app);
+        snapshot.push(format!("{:?}", app.world.resource::<WaveDirector>()));
+        snapshot
+    }
+
+    fn fly_around(app: &mut App, ticks: usize) {
+        for tick in 0..ticks {
+            let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+            keyboard_input.release_all();
+            keyboard_input.press(if tick % 60 < 30 { KeyCode::W } else { KeyCode::A });
+            if tick % 15 == 0 {
+                keyboard_input.press(KeyCode::Space);
+            }
+            run_ticks(app, 1);
+        }
+        app.world.resource_mut::<Input<KeyCode>>().release_all();
+    }
+
+    #[test]
+    fn a_restarted_run_matches_a_fresh_one() {
+        let mut fresh = game_app(SEED);
+        let mut restarted = game_app(SEED);
+        fly_around(&mut restarted, 600);
+        assert_ne!(snapshot(&mut restarted), snapshot(&mut fresh));
+
+        restarted.world.send_event(RestartRun);
+        restarted.update();
+        assert_eq!(snapshot(&mut restarted), snapshot(&mut fresh));
+
+        // hidden state such as the rng or timers shows up once both runs play on
+        fly_around(&mut fresh, 600);
+        fly_around(&mut restarted, 600);
+        assert_eq!(snapshot(&mut restarted), snapshot(&mut fresh));
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use crate::state::GameState;
 
+const TICK_RATE_HZ: f64 = 60.0;
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum InGameSet {
     UserInput,
@@ -9,28 +11,39 @@ pub enum InGameSet {
     DespawnEntities,
 }
 
-pub struct SchedulePlugin;
+/// Runs the `InGameSet` simulation in `FixedUpdate`, `tick_rate_hz` times per second.
+pub struct SchedulePlugin {
+    pub tick_rate_hz: f64,
+}
+
+impl Default for SchedulePlugin {
+    fn default() -> Self {
+        Self { tick_rate_hz: TICK_RATE_HZ }
+    }
+}
 
 impl Plugin for SchedulePlugin {
     fn build(&self, app: &mut App) {
-        app.configure_sets(
-            Update,
-            (
-                InGameSet::DespawnEntities,
-                // flush commands ('apply_deferred' runs)
-                InGameSet::UserInput,
-                InGameSet::EntityUpdates,
-                InGameSet::CollisionDetection,
+        app
+            .insert_resource(Time::<Fixed>::from_hz(self.tick_rate_hz))
+            .configure_sets(
+                FixedUpdate,
+                (
+                    InGameSet::DespawnEntities,
+                    // flush commands ('apply_deferred' runs)
+                    InGameSet::UserInput,
+                    InGameSet::EntityUpdates,
+                    InGameSet::CollisionDetection,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
             )
-                .chain()
-                .run_if(in_state(GameState::InGame)),
-        )
             .add_systems(
-                Update,
+                FixedUpdate,
                 apply_deferred
                     .after(InGameSet::DespawnEntities)
                     .before(InGameSet::UserInput)
             )
         ;
     }
-}
\ No newline at end of file
+}
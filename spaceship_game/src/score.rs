@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::despawn::despawn_dead_entities;
+use crate::health::Killed;
+use crate::schedule::InGameSet;
+use crate::spaceship::Spaceship;
+use crate::run_session::{ResetRun, RunSessionSet};
+use crate::state::GameState;
+
+const HIGH_SCORES_PATH: &str = "highscores.ron";
+const HIGH_SCORES_KEPT: usize = 10;
+
+const COMBO_WINDOW_SECONDS: f32 = 2.0;
+const MAX_COMBO_MULTIPLIER: u32 = 8;
+
+/// Points the spaceship gets for killing this entity, before the combo multiplier.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ScoreValue(pub u32);
+
+/// Kills in quick succession raise the multiplier, it drops back to 1 once `COMBO_WINDOW_SECONDS` pass without one.
+#[derive(Resource, Debug)]
+pub struct Score {
+    pub points: u32,
+    pub combo_multiplier: u32,
+    combo_timer: Timer,
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            combo_multiplier: 1,
+            combo_timer: Timer::from_seconds(COMBO_WINDOW_SECONDS, TimerMode::Once),
+        }
+    }
+}
+
+impl Score {
+    pub fn add_kill(&mut self, points: u32) {
+        if !self.combo_timer.finished() && self.points > 0 {
+            self.combo_multiplier = (self.combo_multiplier + 1).min(MAX_COMBO_MULTIPLIER);
+        }
+        self.points += points * self.combo_multiplier;
+        self.combo_timer.reset();
+    }
+}
+
+/// Best scores, highest first, persisted in `HighScoresPath`.
+#[derive(Resource, Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    pub scores: Vec<u32>,
+}
+
+impl HighScores {
+    pub fn insert(&mut self, score: u32) {
+        let index = self.scores.partition_point(|&high_score| high_score >= score);
+        self.scores.insert(index, score);
+        self.scores.truncate(HIGH_SCORES_KEPT);
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        ron::from_str(&contents).unwrap_or_else(|err| {
+            warn!("Ignoring invalid high scores in {}: {}", path.display(), err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(path, contents).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            error!("Failed to save high scores to {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// File the high scores are loaded from and saved to, without one they only last while the game runs.
+#[derive(Resource, Debug, Clone)]
+pub struct HighScoresPath(pub Option<PathBuf>);
+
+pub struct ScorePlugin {
+    pub high_scores_path: Option<PathBuf>,
+}
+
+impl Default for ScorePlugin {
+    fn default() -> Self {
+        Self { high_scores_path: Some(HIGH_SCORES_PATH.into()) }
+    }
+}
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        let high_scores = self.high_scores_path.as_deref().map(HighScores::load).unwrap_or_default();
+        app
+            .init_resource::<Score>()
+            .insert_resource(high_scores)
+            .insert_resource(HighScoresPath(self.high_scores_path.clone()))
+            .add_systems(
+                FixedUpdate,
+                award_kills
+                    .after(despawn_dead_entities)
+                    .in_set(InGameSet::DespawnEntities),
+            )
+            .add_systems(FixedUpdate, tick_combo.in_set(InGameSet::EntityUpdates))
+            .add_systems(OnEnter(GameState::GameOver), save_high_score)
+            .add_systems(ResetRun, reset_score.in_set(RunSessionSet::Reset))
+        ;
+    }
+}
+
+// victims are still around here, their despawn commands haven't been applied yet
+fn award_kills(mut score: ResMut<Score>,
+               mut killed_reader: EventReader<Killed>,
+               victim_query: Query<&ScoreValue>,
+               spaceship_query: Query<(), With<Spaceship>>) {
+    for &Killed { victim, killer } in killed_reader.read() {
+        if !spaceship_query.contains(killer) {
+            continue;
+        }
+        if let Ok(score_value) = victim_query.get(victim) {
+            score.add_kill(score_value.0);
+        }
+    }
+}
+
+fn tick_combo(mut score: ResMut<Score>, time: Res<Time>) {
+    score.combo_timer.tick(time.delta());
+    if score.combo_timer.just_finished() {
+        score.combo_multiplier = 1;
+    }
+}
+
+fn save_high_score(score: Res<Score>,
+                   mut high_scores: ResMut<HighScores>,
+                   high_scores_path: Res<HighScoresPath>) {
+    high_scores.insert(score.points);
+    if let Some(path) = &high_scores_path.0 {
+        high_scores.save(path);
+    }
+}
+
+fn reset_score(mut score: ResMut<Score>) {
+    *score = Score::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+    use crate::despawn::DespawnPlugin;
+    use crate::health::{Health, HealthPlugin};
+    use crate::testing::{headless_app, run_ticks, start_game, EventCollector};
+    use super::*;
+
+    const POINTS: u32 = 10;
+
+    fn score_app(high_scores_path: Option<PathBuf>) -> App {
+        let mut app = headless_app();
+        app.add_plugins((ScorePlugin { high_scores_path }, HealthPlugin, DespawnPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    /// Spawns an entity `killer` just killed, it's despawned on the next tick.
+    fn spawn_victim(app: &mut App, killer: Entity) -> Entity {
+        let mut health = Health::new(1.0);
+        health.take_damage(1.0, killer);
+        app.world.spawn((health, ScoreValue(POINTS))).id()
+    }
+
+    fn kill(app: &mut App, killer: Entity) {
+        spawn_victim(app, killer);
+        run_ticks(app, 1);
+    }
+
+    fn score(app: &App) -> (u32, u32) {
+        let score = app.world.resource::<Score>();
+        (score.points, score.combo_multiplier)
+    }
+
+    #[test]
+    fn dying_sends_killed_with_the_killer() {
+        let mut app = score_app(None);
+        let mut killed = EventCollector::<Killed>::default();
+        let spaceship = app.world.spawn(Spaceship).id();
+        let victim = spawn_victim(&mut app, spaceship);
+        app.world.spawn(Health::new(0.0));
+        run_ticks(&mut app, 1);
+
+        let killed: Vec<(Entity, Entity)> = killed.read(&app).iter().map(|killed| (killed.victim, killed.killer)).collect();
+        assert_eq!(killed, vec![(victim, spaceship)]);
+    }
+
+    #[test]
+    fn quick_kills_raise_the_multiplier_until_the_combo_window_passes() {
+        let mut app = score_app(None);
+        let spaceship = app.world.spawn(Spaceship).id();
+
+        kill(&mut app, spaceship);
+        assert_eq!(score(&app), (POINTS, 1));
+        run_ticks(&mut app, 60);
+        kill(&mut app, spaceship);
+        assert_eq!(score(&app), (3 * POINTS, 2));
+
+        run_ticks(&mut app, (COMBO_WINDOW_SECONDS * 60.0) as usize + 1);
+        assert_eq!(score(&app), (3 * POINTS, 1));
+        kill(&mut app, spaceship);
+        assert_eq!(score(&app), (4 * POINTS, 1));
+    }
+
+    #[test]
+    fn the_multiplier_is_capped() {
+        let mut app = score_app(None);
+        let spaceship = app.world.spawn(Spaceship).id();
+        let kills = MAX_COMBO_MULTIPLIER + 2;
+        for _ in 0..kills {
+            kill(&mut app, spaceship);
+        }
+
+        // 1 + 2 + ... + MAX, then MAX for every kill after that
+        let multipliers = (1..=MAX_COMBO_MULTIPLIER).sum::<u32>() + 2 * MAX_COMBO_MULTIPLIER;
+        assert_eq!(score(&app), (multipliers * POINTS, MAX_COMBO_MULTIPLIER));
+    }
+
+    #[test]
+    fn only_kills_by_the_spaceship_score() {
+        let mut app = score_app(None);
+        let asteroid = app.world.spawn_empty().id();
+        kill(&mut app, asteroid);
+        let mut unattributed = Health::new(1.0);
+        unattributed.current = 0.0;
+        app.world.spawn((unattributed, ScoreValue(POINTS)));
+        run_ticks(&mut app, 1);
+
+        assert_eq!(score(&app), (0, 1));
+    }
+
+    #[test]
+    fn high_scores_are_saved_on_game_over_and_loaded_at_startup() {
+        let path = env::temp_dir().join(format!("spaceship_game_highscores_{}.ron", process::id()));
+        let mut app = score_app(Some(path.clone()));
+        let spaceship = app.world.spawn(Spaceship).id();
+        kill(&mut app, spaceship);
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+        app.update();
+
+        let app = score_app(Some(path.clone()));
+        let scores = app.world.resource::<HighScores>().scores.clone();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scores, vec![POINTS]);
+    }
+
+    #[test]
+    fn high_scores_keep_the_best_in_order() {
+        let mut high_scores = HighScores::default();
+        for score in 0..HIGH_SCORES_KEPT as u32 + 5 {
+            high_scores.insert(score);
+        }
+        let expected: Vec<u32> = (5..HIGH_SCORES_KEPT as u32 + 5).rev().collect();
+        assert_eq!(high_scores.scores, expected);
+    }
+}
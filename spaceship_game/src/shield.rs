@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use crate::schedule::InGameSet;
+
+const BUBBLE_RADIUS: f32 = 6.0;
+const BUBBLE_COLOR: Color = Color::rgba(0.3, 0.7, 1.0, 0.25);
+
+const ENERGY_DRAIN_PER_SECOND: f32 = 40.0;
+const ENERGY_REGEN_PER_SECOND: f32 = 15.0;
+
+/// Present while the shield is up.
+#[derive(Component, Debug)]
+pub struct SpaceshipShield;
+
+impl SpaceshipShield {
+    /// Share of collision damage the shield stops.
+    pub const ABSORPTION: f32 = 0.8;
+    /// Energy needed to raise the shield.
+    pub const ACTIVATION_ENERGY: f32 = 20.0;
+}
+
+/// Drains while `SpaceshipShield` is up and regenerates while it's down.
+#[derive(Component, Debug)]
+pub struct ShieldEnergy {
+    pub current: f32,
+    pub max: f32,
+    /// Set when the shield runs out, it can't be raised again until the shield key is released,
+    /// so holding the key doesn't flicker it up and down.
+    pub depleted: bool,
+}
+
+impl ShieldEnergy {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max, depleted: false }
+    }
+}
+
+/// Visual of the shield, a child of the entity with `ShieldEnergy`.
+#[derive(Component, Debug)]
+pub struct ShieldBubble;
+
+#[derive(Resource, Debug)]
+struct ShieldAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+pub struct ShieldPlugin;
+
+impl Plugin for ShieldPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, create_shield_assets)
+            .add_systems(FixedUpdate, update_shield_energy.in_set(InGameSet::EntityUpdates))
+            .add_systems(Update, (attach_shield_bubbles, show_shield_bubbles).chain())
+        ;
+    }
+}
+
+fn create_shield_assets(mut commands: Commands,
+                        mut meshes: ResMut<Assets<Mesh>>,
+                        mut materials: ResMut<Assets<StandardMaterial>>) {
+    commands.insert_resource(ShieldAssets {
+        mesh: meshes.add(Mesh::from(shape::UVSphere { radius: BUBBLE_RADIUS, ..default() })),
+        material: materials.add(StandardMaterial {
+            base_color: BUBBLE_COLOR,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+fn update_shield_energy(mut commands: Commands,
+                        mut query: Query<(Entity, &mut ShieldEnergy, Has<SpaceshipShield>)>,
+                        time: Res<Time>) {
+    for (entity, mut energy, shield_up) in query.iter_mut() {
+        if shield_up {
+            energy.current = (energy.current - ENERGY_DRAIN_PER_SECOND * time.delta_seconds()).max(0.0);
+            if energy.current <= 0.0 {
+                energy.depleted = true;
+                commands.entity(entity).remove::<SpaceshipShield>();
+            }
+        } else {
+            energy.current = (energy.current + ENERGY_REGEN_PER_SECOND * time.delta_seconds()).min(energy.max);
+        }
+    }
+}
+
+fn attach_shield_bubbles(mut commands: Commands,
+                         shield_assets: Res<ShieldAssets>,
+                         query: Query<Entity, Added<ShieldEnergy>>) {
+    for entity in query.iter() {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh: shield_assets.mesh.clone(),
+                    material: shield_assets.material.clone(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                ShieldBubble,
+            ));
+        });
+    }
+}
+
+fn show_shield_bubbles(mut bubble_query: Query<(&Parent, &mut Visibility), With<ShieldBubble>>,
+                       shield_query: Query<Has<SpaceshipShield>>) {
+    for (parent, mut visibility) in bubble_query.iter_mut() {
+        let shield_up = shield_query.get(parent.get()).unwrap_or(false);
+        *visibility = if shield_up { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asteroids::ASTEROID_LAYERS;
+    use crate::collision_detection::{Collider, CollisionDamage, CollisionDetectionPlugin};
+    use crate::health::{Health, HealthPlugin};
+    use crate::spaceship::{Spaceship, SpaceshipPlugin};
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    const TICKS_PER_SECOND: f32 = 60.0;
+
+    fn shield_app() -> App {
+        let mut app = headless_app();
+        app.add_plugins((ShieldPlugin, SpaceshipPlugin, HealthPlugin, CollisionDetectionPlugin));
+        start_game(&mut app);
+        app
+    }
+
+    fn hold_shield(app: &mut App, held: bool, seconds: f32) {
+        let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+        if held {
+            keyboard_input.press(KeyCode::Tab);
+        } else {
+            keyboard_input.release(KeyCode::Tab);
+        }
+        run_ticks(app, (seconds * TICKS_PER_SECOND).round() as usize);
+    }
+
+    /// Shield energy, whether the shield is up and the ship's health.
+    fn spaceship(app: &mut App) -> (f32, bool, f32) {
+        let (energy, shield_up, health) = app.world
+            .query_filtered::<(&ShieldEnergy, Has<SpaceshipShield>, &Health), With<Spaceship>>()
+            .single(&app.world);
+        (energy.current, shield_up, health.current)
+    }
+
+    fn collision_damage_taken(shielded: bool) -> f32 {
+        let mut app = shield_app();
+        hold_shield(&mut app, shielded, 0.1);
+        assert_eq!(spaceship(&mut app).1, shielded);
+
+        let translation = app.world.query_filtered::<&Transform, With<Spaceship>>().single(&app.world).translation;
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            Collider::new(1.0),
+            CollisionDamage::new(50.0),
+            ASTEROID_LAYERS,
+        ));
+        // contacts found at the end of one tick deal their damage in the next
+        run_ticks(&mut app, 2);
+        100.0 - spaceship(&mut app).2
+    }
+
+    #[test]
+    fn shield_absorbs_most_collision_damage() {
+        assert!((collision_damage_taken(false) - 50.0).abs() < 0.1);
+        assert!((collision_damage_taken(true) - 50.0 * (1.0 - SpaceshipShield::ABSORPTION)).abs() < 0.1);
+    }
+
+    #[test]
+    fn shield_drains_while_up_and_regenerates_while_down() {
+        let mut app = shield_app();
+        hold_shield(&mut app, true, 1.0);
+        let (energy, shield_up, _) = spaceship(&mut app);
+        assert!(shield_up);
+        assert!((energy - (100.0 - ENERGY_DRAIN_PER_SECOND)).abs() < 1.0, "{}", energy);
+
+        hold_shield(&mut app, false, 1.0);
+        let (regenerated, shield_up, _) = spaceship(&mut app);
+        assert!(!shield_up);
+        assert!((regenerated - energy - ENERGY_REGEN_PER_SECOND).abs() < 1.0, "{}", regenerated);
+
+        // regeneration stops at max
+        hold_shield(&mut app, false, 10.0);
+        assert_eq!(spaceship(&mut app).0, 100.0);
+    }
+
+    #[test]
+    fn depleted_shield_stays_down_until_the_key_is_released() {
+        let mut app = shield_app();
+        hold_shield(&mut app, true, 100.0 / ENERGY_DRAIN_PER_SECOND + 0.1);
+        assert!(!spaceship(&mut app).1);
+
+        // enough energy to raise it again, but the key is still held
+        hold_shield(&mut app, true, 2.0);
+        let (energy, shield_up, _) = spaceship(&mut app);
+        assert!(energy > SpaceshipShield::ACTIVATION_ENERGY);
+        assert!(!shield_up);
+
+        hold_shield(&mut app, false, 0.1);
+        hold_shield(&mut app, true, 0.1);
+        assert!(spaceship(&mut app).1);
+    }
+}
@@ -1,104 +1,97 @@
-use bevy::ecs::schedule::run_enter_schedule;
 use bevy::prelude::*;
-use crate::asset_loader::SceneAssets;
-use crate::collision_detection::{Collider, CollisionDamage};
+use crate::collision_detection::{Collider, CollisionDamage, CollisionLayers, DamageCooldown, Owner};
 use crate::health::Health;
+use crate::input::{ShipInput, ShipInputSet};
+use crate::model_pool::{Model, Models};
 use crate::movement::{Acceleration, MovingObjectBundle, Velocity};
+use crate::particles::{ParticleEffect, ParticleEmitter};
+use crate::play_area::Wraps;
 use crate::schedule::InGameSet;
+use crate::shield::{ShieldEnergy, SpaceshipShield};
+use crate::run_session::{ResetRun, RunSessionSet};
 use crate::state::GameState;
+use crate::weapons::{spawn_projectiles, Weapon, WeaponKind, WeaponRack};
 
 const STARTING_TRANSLATION: Vec3 = Vec3::new(0., 0., -20.);
-const STARTING_VELOCITY: Vec3 = Vec3::new(0., 0., 1.);
 const SPACESHIP_SPEED: f32 = 25.0;
 const SPACESHIP_ROTATION_SPEED: f32 = 2.5;
 const SPACESHIP_ROLL_SPEED: f32 = 2.5;
 const SPACESHIP_RADIUS: f32 = 5.0;
 const SPACESHIP_HEALTH: f32 = 100.0;
+const SPACESHIP_HEALTH_REGENERATION: f32 = 1.0;
 const SPACESHIP_COLLISION_DAMAGE: f32 = 100.0;
+const SPACESHIP_DAMAGE_COOLDOWN_SECONDS: f32 = 1.0;
+const SPACESHIP_INVULNERABILITY_SECONDS: f32 = 1.0;
+const SPACESHIP_SHIELD_ENERGY: f32 = 100.0;
 
-const MISSILE_SPEED: f32 = 35.0;
-const MISSILE_FORWARD_SPAWN_SCALAR: f32 = 7.5;
-const MISSILE_RADIUS: f32 = 0.5;
-const MISSILE_HEALTH: f32 = 0.1;
-const MISSILE_COLLISION_DAMAGE: f32 = 3.5;
+pub const SPACESHIP_LAYERS: CollisionLayers = CollisionLayers::new(CollisionLayers::SPACESHIP, !CollisionLayers::SPACESHIP);
+/// Missiles ignore the ship that fired them.
+pub const SPACESHIP_MISSILE_LAYERS: CollisionLayers = CollisionLayers::new(
+    CollisionLayers::SPACESHIP_MISSILE,
+    !(CollisionLayers::SPACESHIP_MISSILE | CollisionLayers::SPACESHIP),
+);
 
 
 #[derive(Component, Debug)]
 pub struct Spaceship;
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone)]
 pub struct SpaceshipMissile;
 
-#[derive(Component, Debug)]
-pub struct SpaceshipShield;
-
 pub struct SpaceshipPlugin;
 
 impl Plugin for SpaceshipPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(PostStartup, spawn_spaceship)
-            .add_systems(OnEnter(GameState::GameOver), spawn_spaceship)
+            .add_systems(ResetRun, spawn_spaceship.in_set(RunSessionSet::Spawn))
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
                     spaceship_movement_controls,
                     spaceship_weapons_controls,
                     spaceship_shield_controls,
                 )
                     .chain()
-                    .in_set(InGameSet::UserInput),
+                    .in_set(InGameSet::UserInput)
+                    .after(ShipInputSet),
             )
-            .add_systems(Update, spaceship_destroyed.in_set(InGameSet::EntityUpdates))
+            .add_systems(FixedUpdate, spaceship_destroyed.in_set(InGameSet::EntityUpdates))
         ;
     }
 }
 
-fn spawn_spaceship(mut commands: Commands, scene_assets: Res<SceneAssets>) {
-    commands.spawn((
+fn spawn_spaceship(mut commands: Commands, mut models: Models) {
+    models.spawn(&mut commands, Model::Spaceship, (
         MovingObjectBundle {
-            model: SceneBundle {
-                scene: scene_assets.spaceship.clone(),
-                transform: Transform::from_translation(STARTING_TRANSLATION),
-                ..default()
-            },
+            spatial: SpatialBundle::from_transform(Transform::from_translation(STARTING_TRANSLATION)),
             velocity: Velocity::new(Vec3::ZERO),
             acceleration: Acceleration::new(Vec3::ZERO),
             collider: Collider::new(SPACESHIP_RADIUS),
         },
         Spaceship,
-        Health::new(SPACESHIP_HEALTH),
+        Wraps,
+        Health::new(SPACESHIP_HEALTH)
+            .with_regeneration(SPACESHIP_HEALTH_REGENERATION)
+            .with_invulnerability(SPACESHIP_INVULNERABILITY_SECONDS),
         CollisionDamage::new(SPACESHIP_COLLISION_DAMAGE),
+        SPACESHIP_LAYERS,
+        DamageCooldown::new(SPACESHIP_DAMAGE_COOLDOWN_SECONDS),
+        ShieldEnergy::new(SPACESHIP_SHIELD_ENERGY),
+        Weapon::new(WeaponKind::Missile),
+        ParticleEmitter::new(ParticleEffect::Exhaust, 0.0),
+        WeaponRack::new([WeaponKind::SpreadShot, WeaponKind::RapidLaser, WeaponKind::HomingMissile]),
     ));
 }
 
 fn spaceship_movement_controls(mut query: Query<(&mut Transform, &mut Velocity), With<Spaceship>>,
-                               keyboard_input: Res<Input<KeyCode>>,
+                               ship_input: Res<ShipInput>,
                                time: Res<Time>) {
     let Ok((mut transform, mut velocity)) = query.get_single_mut() else {
         return;
     };
-    let mut rotation = 0.0;
-    let mut roll = 0.0;
-    let mut movement = 0.0;
-
-    if keyboard_input.pressed(KeyCode::D) {
-        rotation -= SPACESHIP_ROTATION_SPEED * time.delta_seconds();
-    } else if keyboard_input.pressed(KeyCode::A) {
-        rotation += SPACESHIP_ROTATION_SPEED * time.delta_seconds();
-    }
-
-    if keyboard_input.pressed(KeyCode::S) {
-        movement -= SPACESHIP_SPEED;
-    } else if keyboard_input.pressed(KeyCode::W) {
-        movement += SPACESHIP_SPEED;
-    }
-
-    if keyboard_input.pressed(KeyCode::ShiftLeft) {
-        roll -= SPACESHIP_ROLL_SPEED * time.delta_seconds();
-    } else if keyboard_input.pressed(KeyCode::ControlLeft) {
-        roll += SPACESHIP_ROLL_SPEED * time.delta_seconds();
-    }
+    let rotation = ship_input.turn * SPACESHIP_ROTATION_SPEED * time.delta_seconds();
+    let roll = ship_input.roll * SPACESHIP_ROLL_SPEED * time.delta_seconds();
+    let movement = ship_input.thrust * SPACESHIP_SPEED;
 
     transform.rotate_y(rotation);
     transform.rotate_local_z(roll);
@@ -106,41 +99,36 @@ fn spaceship_movement_controls(mut query: Query<(&mut Transform, &mut Velocity),
 }
 
 fn spaceship_weapons_controls(mut commands: Commands,
-                              scene_assets: Res<SceneAssets>,
-                              mut query: Query<(&mut Transform), With<Spaceship>>,
-                              keyboard_input: Res<Input<KeyCode>>) {
-    let Ok(transform) = query.get_single_mut() else {
+                              mut models: Models,
+                              mut query: Query<(Entity, &Transform, &mut Weapon, &mut WeaponRack), With<Spaceship>>,
+                              ship_input: Res<ShipInput>,
+                              time: Res<Time>) {
+    let Ok((entity, transform, mut weapon, mut rack)) = query.get_single_mut() else {
         return;
     };
 
-    if keyboard_input.pressed(KeyCode::Space) {
-        commands.spawn((
-            MovingObjectBundle {
-                model: SceneBundle {
-                    scene: scene_assets.missiles.clone(),
-                    transform: Transform::from_translation(
-                        transform.translation + -transform.forward() * MISSILE_FORWARD_SPAWN_SCALAR
-                    ),
-                    ..default()
-                },
-                velocity: Velocity::new(-transform.forward() * MISSILE_SPEED),
-                acceleration: Acceleration::new(Vec3::ZERO),
-                collider: Collider::new(MISSILE_RADIUS),
-            },
-            SpaceshipMissile,
-            Health::new(MISSILE_HEALTH),
-            CollisionDamage::new(MISSILE_COLLISION_DAMAGE),
-        ));
+    if ship_input.cycle_weapon && !rack.cycle_held {
+        rack.cycle(&mut weapon);
+        info!("Equipped {:?}", weapon.kind);
+    }
+    rack.cycle_held = ship_input.cycle_weapon;
+
+    weapon.tick(time.delta());
+    if ship_input.fire && weapon.try_fire() {
+        spawn_projectiles(&mut commands, &mut models, &weapon, transform, SPACESHIP_MISSILE_LAYERS, (SpaceshipMissile, Owner(entity)));
     }
 }
 
 fn spaceship_shield_controls(mut commands: Commands,
-                             mut query: Query<(Entity), With<Spaceship>>,
-                             keyboard_input: Res<Input<KeyCode>>) {
-    let Ok(entity) = query.get_single() else {
+                             mut query: Query<(Entity, &mut ShieldEnergy, Has<SpaceshipShield>), With<Spaceship>>,
+                             ship_input: Res<ShipInput>) {
+    let Ok((entity, mut energy, shield_up)) = query.get_single_mut() else {
         return;
     };
-    if keyboard_input.pressed(KeyCode::Tab) {
+    if !ship_input.shield {
+        energy.depleted = false;
+        commands.entity(entity).remove::<SpaceshipShield>();
+    } else if !shield_up && !energy.depleted && energy.current >= SpaceshipShield::ACTIVATION_ENERGY {
         commands.entity(entity).insert(SpaceshipShield);
     }
 }
@@ -0,0 +1,347 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use crate::collision_detection::CollisionLayers;
+
+const CELL_SIZE: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy)]
+struct GridEntry {
+    /// Index into `entities`, shared by a sphere and its images.
+    id: usize,
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+    layers: CollisionLayers,
+    min_cell: IVec3,
+}
+
+/// Uniform grid broadphase, rebuilt every frame by `collision_detection`.
+///
+/// Buckets, entries and pairs are cleared rather than dropped, so steady-state frames don't allocate.
+#[derive(Resource, Debug)]
+pub struct SpatialHashGrid {
+    cell_size: f32,
+    entities: Vec<Entity>,
+    entries: Vec<GridEntry>,
+    cells: HashMap<IVec3, Vec<usize>>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl Default for SpatialHashGrid {
+    fn default() -> Self {
+        Self::new(CELL_SIZE)
+    }
+}
+
+impl SpatialHashGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            entities: vec![],
+            entries: vec![],
+            cells: HashMap::new(),
+            pairs: vec![],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+        self.entries.clear();
+        self.pairs.clear();
+        // keep the buckets that were used last frame, their allocations will most likely be reused
+        self.cells.retain(|_, bucket| {
+            let used = !bucket.is_empty();
+            bucket.clear();
+            used
+        });
+    }
+
+    /// Inserts a sphere and returns its id, to be used with `insert_image`.
+    pub fn insert(&mut self, entity: Entity, center: Vec3, radius: f32, layers: CollisionLayers) -> usize {
+        self.insert_swept(entity, center, center, radius, layers)
+    }
+
+    /// Inserts a sphere moving from `start` to `end` during the frame and returns its id.
+    pub fn insert_swept(&mut self, entity: Entity, start: Vec3, end: Vec3, radius: f32, layers: CollisionLayers) -> usize {
+        let id = self.entities.len();
+        self.entities.push(entity);
+        self.push_entry(id, start, end, radius, layers);
+        id
+    }
+
+    /// Inserts another copy of the sphere with the given id, e.g. its image across the seam of a wrapping play area.
+    /// Collisions with any copy are reported for the original entity.
+    pub fn insert_image(&mut self, id: usize, center: Vec3, radius: f32, layers: CollisionLayers) {
+        self.push_entry(id, center, center, radius, layers);
+    }
+
+    fn push_entry(&mut self, id: usize, start: Vec3, end: Vec3, radius: f32, layers: CollisionLayers) {
+        let index = self.entries.len();
+        let (min_cell, max_cell) = self.cell_range(start.min(end), start.max(end), radius);
+        self.entries.push(GridEntry { id, start, end, radius, layers, min_cell });
+
+        for x in min_cell.x..=max_cell.x {
+            for y in min_cell.y..=max_cell.y {
+                for z in min_cell.z..=max_cell.z {
+                    self.cells.entry(IVec3::new(x, y, z)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    /// Finds every overlapping pair of inserted spheres whose layers interact.
+    /// Swept spheres overlap if they come close enough at any point along their motion.
+    /// Pairs are reported once and sorted by insertion order, so results match a brute-force pass.
+    pub fn find_pairs(&mut self) {
+        self.pairs.clear();
+        for (&cell, bucket) in self.cells.iter() {
+            for (i, &index_a) in bucket.iter().enumerate() {
+                let a = &self.entries[index_a];
+                for &index_b in bucket[i + 1..].iter() {
+                    let b = &self.entries[index_b];
+                    // a pair that shares several cells is only tested in the lowest one of them
+                    if a.min_cell.max(b.min_cell) != cell {
+                        continue;
+                    }
+                    if a.id == b.id || !a.layers.interacts_with(&b.layers) {
+                        continue;
+                    }
+                    if closest_approach(a, b) < a.radius + b.radius {
+                        self.pairs.push((a.id.min(b.id), a.id.max(b.id)));
+                    }
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+        // images can find the same pair more than once
+        self.pairs.dedup();
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.pairs
+            .iter()
+            .map(|&(a, b)| (self.entities[a], self.entities[b]))
+    }
+
+    fn cell_range(&self, min: Vec3, max: Vec3, radius: f32) -> (IVec3, IVec3) {
+        let min = ((min - Vec3::splat(radius)) / self.cell_size).floor().as_ivec3();
+        let max = ((max + Vec3::splat(radius)) / self.cell_size).floor().as_ivec3();
+        (min, max)
+    }
+}
+
+/// Smallest distance between the centers of two spheres moving linearly over the same frame.
+fn closest_approach(a: &GridEntry, b: &GridEntry) -> f32 {
+    let offset = a.start - b.start;
+    let relative_motion = (a.end - a.start) - (b.end - b.start);
+    let motion_length_squared = relative_motion.length_squared();
+    if motion_length_squared <= f32::EPSILON {
+        return offset.length();
+    }
+    let t = (-offset.dot(relative_motion) / motion_length_squared).clamp(0.0, 1.0);
+    (offset + relative_motion * t).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    const SCENES: u64 = 50;
+    const SPHERES_PER_SCENE: u32 = 200;
+    const SCENE_HALF_EXTENT: f32 = 60.0;
+    /// Positions checked along each swept sphere's path by the brute-force pass.
+    const SWEEP_SAMPLES: usize = 128;
+    const SWEPT_SCENES: u64 = 10;
+    const BENCHMARK_COLLIDERS: [u32; 4] = [100, 200, 400, 800];
+    const BENCHMARK_FRAMES: u32 = 20;
+
+    /// Sphere of a random scene, as a collider with its `Transform` and layers.
+    struct Sphere {
+        entity: Entity,
+        start: Vec3,
+        end: Vec3,
+        radius: f32,
+        layers: CollisionLayers,
+        /// Extra copies, like across the seam of a wrapping play area.
+        images: Vec<Vec3>,
+    }
+
+    impl Sphere {
+        /// Paths of the sphere and its images over the frame, images don't move.
+        fn paths(&self) -> Vec<(Vec3, Vec3)> {
+            let images = self.images.iter().map(|&image| (image, image));
+            [(self.start, self.end)].into_iter().chain(images).collect()
+        }
+    }
+
+    fn random_vec3(rng: &mut StdRng, half_extent: f32) -> Vec3 {
+        Vec3::new(
+            rng.gen_range(-half_extent..half_extent),
+            0.0,
+            rng.gen_range(-half_extent..half_extent),
+        )
+    }
+
+    fn random_layers(rng: &mut StdRng) -> CollisionLayers {
+        let layers = [
+            CollisionLayers::SPACESHIP,
+            CollisionLayers::SPACESHIP_MISSILE,
+            CollisionLayers::ASTEROID,
+            CollisionLayers::ENEMY,
+        ];
+        let membership = layers[rng.gen_range(0..layers.len())];
+        CollisionLayers::new(membership, !membership)
+    }
+
+    fn random_scene(rng: &mut StdRng, spheres: u32, swept_and_images: bool) -> Vec<Sphere> {
+        (0..spheres)
+            .map(|index| {
+                let start = random_vec3(rng, SCENE_HALF_EXTENT);
+                let end = if swept_and_images && rng.gen_bool(0.2) { start + random_vec3(rng, 30.0) } else { start };
+                let images = if swept_and_images && rng.gen_bool(0.05) {
+                    vec![start + random_vec3(rng, SCENE_HALF_EXTENT)]
+                } else {
+                    vec![]
+                };
+                Sphere {
+                    entity: Entity::from_raw(index),
+                    start,
+                    end,
+                    // mostly small spheres, some spanning several cells
+                    radius: if rng.gen_bool(0.1) { rng.gen_range(5.0..25.0) } else { rng.gen_range(0.3..3.0) },
+                    layers: random_layers(rng),
+                    images,
+                }
+            })
+            .collect()
+    }
+
+    fn fill_grid(grid: &mut SpatialHashGrid, scene: &[Sphere]) {
+        // the grid is reused like it is between frames
+        grid.clear();
+        for sphere in scene {
+            let id = grid.insert_swept(sphere.entity, sphere.start, sphere.end, sphere.radius, sphere.layers);
+            for &image in sphere.images.iter() {
+                grid.insert_image(id, image, sphere.radius, sphere.layers);
+            }
+        }
+        grid.find_pairs();
+    }
+
+    /// The collision check from before the broadphase, every sphere against every other one by distance.
+    fn brute_force_pairs(scene: &[Sphere]) -> Vec<(Entity, Entity)> {
+        let mut pairs = vec![];
+        for (i, a) in scene.iter().enumerate() {
+            for b in scene[i + 1..].iter() {
+                if a.layers.interacts_with(&b.layers) && a.start.distance(b.start) < a.radius + b.radius {
+                    pairs.push((a.entity, b.entity));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Smallest distance between two spheres or their images, sampled along their paths,
+    /// and a lower bound for the exact closest approach, as sampling can step over it.
+    fn sampled_closest_approach(a: &Sphere, b: &Sphere) -> (f32, f32) {
+        let mut closest = f32::INFINITY;
+        let mut lower_bound = f32::INFINITY;
+        for (start_a, end_a) in a.paths() {
+            for (start_b, end_b) in b.paths() {
+                let sampled = (0..=SWEEP_SAMPLES)
+                    .map(|step| {
+                        let t = step as f32 / SWEEP_SAMPLES as f32;
+                        start_a.lerp(end_a, t).distance(start_b.lerp(end_b, t))
+                    })
+                    .fold(f32::INFINITY, f32::min);
+                let step = ((end_a - start_a) - (end_b - start_b)).length() / SWEEP_SAMPLES as f32;
+                closest = closest.min(sampled);
+                lower_bound = lower_bound.min(sampled - step / 2.0);
+            }
+        }
+        (closest, lower_bound)
+    }
+
+    #[test]
+    fn find_pairs_matches_brute_force_on_random_scenes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut grid = SpatialHashGrid::default();
+        for _ in 0..SCENES {
+            let scene = random_scene(&mut rng, SPHERES_PER_SCENE, false);
+            fill_grid(&mut grid, &scene);
+
+            let expected = brute_force_pairs(&scene);
+            assert!(!expected.is_empty());
+            assert_eq!(grid.pairs().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn find_pairs_matches_sampled_sweeps_and_images_on_random_scenes() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut grid = SpatialHashGrid::default();
+        for _ in 0..SWEPT_SCENES {
+            let scene = random_scene(&mut rng, SPHERES_PER_SCENE, true);
+            fill_grid(&mut grid, &scene);
+            let found: Vec<(Entity, Entity)> = grid.pairs().collect();
+
+            for (i, a) in scene.iter().enumerate() {
+                for b in scene[i + 1..].iter() {
+                    let (closest, lower_bound) = sampled_closest_approach(a, b);
+                    let reach = a.radius + b.radius;
+                    let pair = (a.entity, b.entity);
+                    let interacts = a.layers.interacts_with(&b.layers);
+                    if interacts && closest < reach {
+                        assert!(found.contains(&pair), "{:?} missed", pair);
+                    }
+                    if found.contains(&pair) {
+                        assert!(interacts && lower_bound < reach, "{:?} found", pair);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swept_spheres_collide_along_their_path() {
+        let mut grid = SpatialHashGrid::default();
+        let layers = CollisionLayers::default();
+        // both end up far apart, but cross each other halfway through the frame
+        grid.insert_swept(Entity::from_raw(0), Vec3::new(-50.0, 0.0, 0.0), Vec3::new(50.0, 0.0, 0.0), 0.5, layers);
+        grid.insert(Entity::from_raw(1), Vec3::ZERO, 1.0, layers);
+        grid.find_pairs();
+        assert_eq!(grid.pairs().collect::<Vec<_>>(), vec![(Entity::from_raw(0), Entity::from_raw(1))]);
+    }
+
+    fn time_frames(mut frame: impl FnMut()) -> Duration {
+        let start = Instant::now();
+        for _ in 0..BENCHMARK_FRAMES {
+            frame();
+        }
+        start.elapsed() / BENCHMARK_FRAMES
+    }
+
+    // cargo test benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn benchmark_grid_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut grid = SpatialHashGrid::default();
+        for colliders in BENCHMARK_COLLIDERS {
+            // sized like the game's missiles, asteroids and ships
+            let mut scene = random_scene(&mut rng, colliders, false);
+            for sphere in scene.iter_mut() {
+                sphere.radius = rng.gen_range(0.3..5.0);
+            }
+            let brute_force = time_frames(|| {
+                std::hint::black_box(brute_force_pairs(&scene));
+            });
+            let broadphase = time_frames(|| fill_grid(&mut grid, &scene));
+            println!("{} colliders: brute force {:?}, grid {:?} per frame", colliders, brute_force, broadphase);
+            assert_eq!(grid.pairs().collect::<Vec<_>>(), brute_force_pairs(&scene));
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use bevy::prelude::*;
+use crate::input::{ActionInput, ShipAction};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, States)]
 pub enum GameState {
     #[default]
+    MainMenu,
     InGame,
     Paused,
+    Settings,
     GameOver,
 }
 
@@ -15,16 +18,15 @@ impl Plugin for StatePlugin {
         app
             .add_state::<GameState>()
             .add_systems(Update, game_state_input_events)
-            .add_systems(Update, transition_to_in_game.run_if(in_state(GameState::GameOver)))
         ;
     }
 }
 
 pub fn game_state_input_events(mut next_state: ResMut<NextState<GameState>>,
                                state: Res<State<GameState>>,
-                               keyboard_input: Res<Input<KeyCode>>,
+                               action_input: ActionInput,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+    if action_input.just_pressed(ShipAction::Pause) {
         match state.get() {
             GameState::InGame => next_state.set(GameState::Paused),
             GameState::Paused => next_state.set(GameState::InGame),
@@ -32,8 +34,3 @@ pub fn game_state_input_events(mut next_state: ResMut<NextState<GameState>>,
         }
     }
 }
-
-pub fn transition_to_in_game(mut next_state: ResMut<NextState<GameState>>,
-) {
-    next_state.set(GameState::InGame)
-}
\ No newline at end of file
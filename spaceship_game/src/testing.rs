@@ -0,0 +1,189 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::thread;
+use std::time::Duration;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::schedule::ExecutorKind;
+use bevy::input::InputPlugin as BevyInputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use crate::asset_loader::SceneAssets;
+use crate::asteroids::AsteroidPlugin;
+use crate::collision_detection::{Collider, CollisionDetectionPlugin};
+use crate::despawn::DespawnPlugin;
+use crate::enemies::EnemyPlugin;
+use crate::health::{Health, HealthPlugin};
+use crate::input::InputPlugin;
+use crate::model_pool::ModelPool;
+use crate::movement::{MovementPlugin, Velocity};
+use crate::particles::ParticlePlugin;
+use crate::pickups::{DropTable, PickupPlugin};
+use crate::play_area::{PlayArea, PlayAreaPlugin};
+use crate::replay::ReplayPlugin;
+use crate::rng::{GameRng, RngPlugin};
+use crate::run_session::RunSessionPlugin;
+use crate::schedule::SchedulePlugin;
+use crate::score::{Score, ScorePlugin};
+use crate::shield::ShieldPlugin;
+use crate::spaceship::SpaceshipPlugin;
+use crate::state::{GameState, StatePlugin};
+use crate::waves::{WaveConfig, WavePlugin};
+use crate::weapons::WeaponsPlugin;
+
+const TICK_RATE_HZ: f64 = 60.0;
+const ASSET_LOAD_ATTEMPTS: usize = 1000;
+
+/// App without a window or renderer that runs exactly one fixed tick per `App::update`,
+/// with the schedule, state and run session the gameplay plugins build on.
+pub fn headless_app() -> App {
+    headless_app_with_tick_rate(TICK_RATE_HZ)
+}
+
+pub fn headless_app_with_tick_rate(tick_rate_hz: f64) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins((MinimalPlugins, AssetPlugin::default(), BevyInputPlugin))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_resource::<SceneAssets>()
+        .init_resource::<ModelPool>()
+        .init_resource::<PlayArea>()
+        .add_plugins(SchedulePlugin { tick_rate_hz })
+        .add_plugins(StatePlugin)
+        .add_plugins(InputPlugin { bindings_path: None })
+        .add_plugins(RunSessionPlugin)
+        .insert_resource(tick_duration(tick_rate_hz))
+    ;
+    app
+}
+
+/// Headless app with every gameplay plugin and a fixed seed, started once the wave and drop files loaded.
+pub fn game_app(seed: u64) -> App {
+    let mut app = headless_app();
+    app
+        .add_plugins(RngPlugin)
+        .add_plugins((SpaceshipPlugin, ShieldPlugin, WeaponsPlugin, AsteroidPlugin, EnemyPlugin, PickupPlugin))
+        .add_plugins((ParticlePlugin, WavePlugin, MovementPlugin, CollisionDetectionPlugin, DespawnPlugin))
+        .add_plugins((HealthPlugin, PlayAreaPlugin, ScorePlugin { high_scores_path: None }, ReplayPlugin))
+        .insert_resource(GameRng::from_seed(seed))
+        // time stands still until the game started, so like `start_game` it doesn't tick yet
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+    ;
+    wait_for_assets(&mut app);
+    start_game(&mut app);
+    app.insert_resource(tick_duration(TICK_RATE_HZ));
+    app
+}
+
+fn wait_for_assets(app: &mut App) {
+    for _ in 0..ASSET_LOAD_ATTEMPTS {
+        app.update();
+        if !app.world.resource::<Assets<WaveConfig>>().is_empty() && !app.world.resource::<Assets<DropTable>>().is_empty() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("wave and drop files didn't load");
+}
+
+fn tick_duration(tick_rate_hz: f64) -> TimeUpdateStrategy {
+    TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / tick_rate_hz))
+}
+
+/// Runs startup and switches to `GameState::InGame`, the first update doesn't tick yet.
+pub fn start_game(app: &mut App) {
+    app.world.resource_mut::<NextState<GameState>>().set(GameState::InGame);
+    app.update();
+}
+
+/// Every gameplay entity and the score, in an order that doesn't depend on entity ids.
+pub fn world_snapshot(app: &mut App) -> Vec<String> {
+    let mut snapshot: Vec<String> = app.world
+        .query_filtered::<(&Transform, Option<&Velocity>, Option<&Health>), With<Collider>>()
+        .iter(&app.world)
+        .map(|(transform, velocity, health)| {
+            let health = health.map(|health| (health.current, health.max, health.is_invulnerable()));
+            format!("{:?} {:?} {:?}", transform.translation, velocity.map(|velocity| velocity.value), health)
+        })
+        .collect();
+    snapshot.sort();
+    snapshot.push(format!("{:?}", app.world.resource::<Score>()));
+    snapshot
+}
+
+pub fn run_ticks(app: &mut App, ticks: usize) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+/// Reads events across updates. Depending on fixed ticks, events can stay buffered
+/// for more than one update, so tests read them like systems do instead of per update.
+pub struct EventCollector<E: Event>(ManualEventReader<E>);
+
+impl<E: Event> Default for EventCollector<E> {
+    fn default() -> Self {
+        Self(ManualEventReader::default())
+    }
+}
+
+impl<E: Event> EventCollector<E> {
+    /// Events sent since the last call.
+    pub fn read<'a>(&'a mut self, app: &'a App) -> Vec<&'a E> {
+        self.0.read(app.world.resource::<Events<E>>()).collect()
+    }
+}
+
+/// Counts allocations for `count_allocations`, on the thread that's counting only.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn count_allocation() {
+    // the thread local may already be gone while a thread shuts down
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        if let Some(count) = allocations.get() {
+            allocations.set(Some(count + 1));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` and returns how many allocations it made on this thread, see `run_on_this_thread`.
+pub fn count_allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|allocations| allocations.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|allocations| allocations.take()).unwrap_or_default()
+}
+
+/// Runs every system of the app on the thread calling `App::update`, so `count_allocations` sees them.
+pub fn run_on_this_thread(app: &mut App) {
+    for (_, schedule) in app.world.resource_mut::<Schedules>().iter_mut() {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    }
+}
@@ -0,0 +1,382 @@
+use std::mem;
+use std::ops::Range;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use crate::asset_loader::RonAssetLoader;
+use crate::asteroids::{spawn_asteroid, split_asteroids, AsteroidSize};
+use crate::enemies::spawn_ufo;
+use crate::model_pool::Models;
+use crate::pickups::drop_pickups;
+use crate::rng::GameRng;
+use crate::run_session::{ResetRun, RunSessionSet};
+use crate::schedule::InGameSet;
+
+const WAVES_PATH: &str = "default.waves.ron";
+const ACCELERATION_SCALAR: f32 = 1.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnRegion {
+    pub x: Range<f32>,
+    pub z: Range<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wave {
+    pub count: u32,
+    pub spawn_interval: f32,
+    pub spawn_regions: Vec<SpawnRegion>,
+    pub speed: Range<f32>,
+    /// Health of the large asteroids the wave spawns, their fragments use their size tier's health.
+    pub health: f32,
+    /// Seconds between this wave being cleared and the next one starting.
+    pub gap_after: f32,
+    /// UFOs spawned when the wave starts.
+    #[serde(default)]
+    pub ufos: u32,
+}
+
+/// Wave definitions, loaded from a `.waves.ron` asset.
+/// Once the last wave is cleared it keeps repeating.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct WaveConfig {
+    pub waves: Vec<Wave>,
+}
+
+impl WaveConfig {
+    pub fn wave(&self, index: usize) -> Option<&Wave> {
+        self.waves.get(index).or(self.waves.last())
+    }
+}
+
+#[derive(Debug)]
+enum WavePhase {
+    NotStarted,
+    Spawning { spawned: u32, timer: Timer },
+    Clearing,
+    Gap { timer: Timer },
+}
+
+/// Marks entities that have to be destroyed before a wave counts as cleared.
+#[derive(Component, Debug)]
+pub struct Hostile;
+
+#[derive(Resource, Debug)]
+pub struct WaveDirector {
+    config: Handle<WaveConfig>,
+    /// Zero-based index of the current wave.
+    wave: usize,
+    phase: WavePhase,
+    /// Asteroids the director asked for this tick, spawned by `spawn_wave_entities`.
+    pending_spawns: u32,
+    /// UFOs the director asked for this tick, spawned by `spawn_wave_entities`.
+    pending_ufo_spawns: u32,
+}
+
+impl WaveDirector {
+    /// One-based number of the current wave.
+    pub fn wave_number(&self) -> usize {
+        self.wave + 1
+    }
+}
+
+#[derive(Event, Debug)]
+pub struct WaveStarted {
+    pub wave: usize,
+}
+
+#[derive(Event, Debug)]
+pub struct WaveCleared {
+    pub wave: usize,
+}
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<WaveConfig>()
+            .register_asset_loader(RonAssetLoader::<WaveConfig>::new(&["waves.ron"]))
+            .add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_systems(Startup, load_waves)
+            .add_systems(ResetRun, reset_wave_director.in_set(RunSessionSet::Reset))
+            .add_systems(
+                FixedUpdate,
+                // fragments and drops have to exist before the director checks for a cleared wave,
+                // and every `GameRng` user runs in a fixed order so seeded runs repeat
+                (apply_deferred, run_wave_director, spawn_wave_entities)
+                    .chain()
+                    .after(split_asteroids)
+                    .after(drop_pickups)
+                    .in_set(InGameSet::EntityUpdates),
+            )
+        ;
+    }
+}
+
+fn load_waves(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WaveDirector {
+        config: asset_server.load(WAVES_PATH),
+        wave: 0,
+        phase: WavePhase::NotStarted,
+        pending_spawns: 0,
+        pending_ufo_spawns: 0,
+    });
+}
+
+fn reset_wave_director(mut director: ResMut<WaveDirector>) {
+    director.wave = 0;
+    director.phase = WavePhase::NotStarted;
+    director.pending_spawns = 0;
+    director.pending_ufo_spawns = 0;
+}
+
+fn run_wave_director(mut director: ResMut<WaveDirector>,
+                     mut started_writer: EventWriter<WaveStarted>,
+                     mut cleared_writer: EventWriter<WaveCleared>,
+                     wave_configs: Res<Assets<WaveConfig>>,
+                     hostile_query: Query<(), With<Hostile>>,
+                     time: Res<Time>) {
+    let Some(wave) = wave_configs.get(&director.config).and_then(|config| config.wave(director.wave)) else {
+        return;
+    };
+    let director = &mut *director;
+
+    match &mut director.phase {
+        WavePhase::NotStarted => {
+            director.phase = start_wave(wave);
+            director.pending_ufo_spawns += wave.ufos;
+            started_writer.send(WaveStarted { wave: director.wave_number() });
+        }
+        WavePhase::Spawning { spawned, timer } => {
+            timer.tick(time.delta());
+            let spawns = timer.times_finished_this_tick().min(wave.count - *spawned);
+            director.pending_spawns += spawns;
+            *spawned += spawns;
+            if *spawned >= wave.count {
+                director.phase = WavePhase::Clearing;
+            }
+        }
+        WavePhase::Clearing => {
+            if hostile_query.is_empty() {
+                cleared_writer.send(WaveCleared { wave: director.wave_number() });
+                director.phase = WavePhase::Gap { timer: Timer::from_seconds(wave.gap_after, TimerMode::Once) };
+            }
+        }
+        WavePhase::Gap { timer } => {
+            timer.tick(time.delta());
+            if timer.finished() {
+                director.wave += 1;
+                director.phase = WavePhase::NotStarted;
+            }
+        }
+    }
+}
+
+fn start_wave(wave: &Wave) -> WavePhase {
+    WavePhase::Spawning {
+        spawned: 0,
+        timer: Timer::from_seconds(wave.spawn_interval, TimerMode::Repeating),
+    }
+}
+
+fn spawn_wave_entities(mut commands: Commands,
+                       mut director: ResMut<WaveDirector>,
+                       mut rng: ResMut<GameRng>,
+                       wave_configs: Res<Assets<WaveConfig>>,
+                       mut models: Models) {
+    let spawns = mem::take(&mut director.pending_spawns);
+    let ufo_spawns = mem::take(&mut director.pending_ufo_spawns);
+    let Some(wave) = wave_configs.get(&director.config).and_then(|config| config.wave(director.wave)) else {
+        return;
+    };
+    for _ in 0..spawns {
+        spawn_wave_asteroid(&mut commands, &mut models, &mut rng, wave);
+    }
+    for _ in 0..ufo_spawns {
+        spawn_wave_ufo(&mut commands, &mut models, &mut rng, wave);
+    }
+}
+
+fn spawn_wave_asteroid(commands: &mut Commands,
+                       models: &mut Models,
+                       rng: &mut GameRng,
+                       wave: &Wave) {
+    let Some(region) = pick(rng, &wave.spawn_regions) else {
+        return;
+    };
+    let translation = Vec3::new(sample(rng, &region.x), 0.0, sample(rng, &region.z));
+    let velocity = random_unit_vector(rng) * sample(rng, &wave.speed);
+    let acceleration = random_unit_vector(rng) * ACCELERATION_SCALAR;
+    spawn_asteroid(commands, models, AsteroidSize::Large, translation, velocity, acceleration, wave.health);
+}
+
+fn spawn_wave_ufo(commands: &mut Commands,
+                  models: &mut Models,
+                  rng: &mut GameRng,
+                  wave: &Wave) {
+    let Some(region) = pick(rng, &wave.spawn_regions) else {
+        return;
+    };
+    let translation = Vec3::new(sample(rng, &region.x), 0.0, sample(rng, &region.z));
+    let strafe_direction = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+    spawn_ufo(commands, models, translation, strafe_direction);
+}
+
+fn pick<'a, T>(rng: &mut GameRng, items: &'a [T]) -> Option<&'a T> {
+    if items.is_empty() {
+        return None;
+    }
+    items.get(rng.gen_range(0..items.len()))
+}
+
+fn sample(rng: &mut GameRng, range: &Range<f32>) -> f32 {
+    if range.is_empty() {
+        return range.start;
+    }
+    rng.gen_range(range.clone())
+}
+
+fn random_unit_vector(rng: &mut GameRng) -> Vec3 {
+    Vec3::new(rng.gen_range(-1.0..1.0), 0., rng.gen_range(-1.0..1.0)).normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asteroids::{Asteroid, AsteroidPlugin};
+    use crate::despawn::DespawnPlugin;
+    use crate::enemies::Ufo;
+    use crate::health::{Health, HealthPlugin};
+    use crate::testing::{headless_app, run_ticks, start_game, EventCollector};
+    use super::*;
+
+    const TICKS_PER_SECOND: f32 = 60.0;
+    const WAVES: &str = "(
+        waves: [
+            (
+                count: 3,
+                spawn_interval: 0.5,
+                spawn_regions: [(x: (start: 20.0, end: 30.0), z: (start: 20.0, end: 30.0))],
+                speed: (start: 0.0, end: 0.0),
+                health: 35.0,
+                gap_after: 1.0,
+            ),
+            (
+                count: 2,
+                spawn_interval: 0.25,
+                spawn_regions: [(x: (start: -30.0, end: -20.0), z: (start: -30.0, end: -20.0))],
+                speed: (start: 0.0, end: 0.0),
+                health: 35.0,
+                gap_after: 1.0,
+                ufos: 1,
+            ),
+        ],
+    )";
+
+    fn wave_app() -> App {
+        let mut app = headless_app();
+        app
+            .add_plugins((WavePlugin, AsteroidPlugin, HealthPlugin, DespawnPlugin))
+            .insert_resource(GameRng::from_seed(0));
+        start_game(&mut app);
+
+        let config: WaveConfig = ron::from_str(WAVES).unwrap();
+        let config = app.world.resource_mut::<Assets<WaveConfig>>().add(config);
+        app.world.resource_mut::<WaveDirector>().config = config;
+        app
+    }
+
+    /// Wave numbers started and cleared, read every tick since events don't outlive two updates.
+    #[derive(Default)]
+    struct WaveLog {
+        started: Vec<usize>,
+        cleared: Vec<usize>,
+        started_reader: EventCollector<WaveStarted>,
+        cleared_reader: EventCollector<WaveCleared>,
+    }
+
+    impl WaveLog {
+        fn run_seconds(&mut self, app: &mut App, seconds: f32) {
+            for _ in 0..(seconds * TICKS_PER_SECOND).round() as usize {
+                run_ticks(app, 1);
+                self.started.extend(self.started_reader.read(app).iter().map(|started| started.wave));
+                self.cleared.extend(self.cleared_reader.read(app).iter().map(|cleared| cleared.wave));
+            }
+        }
+    }
+
+    fn count<T: Component>(app: &mut App) -> usize {
+        app.world.query_filtered::<(), With<T>>().iter(&app.world).count()
+    }
+
+    fn destroy_hostiles(app: &mut App) {
+        let hostiles: Vec<Entity> = app.world.query_filtered::<Entity, With<Hostile>>().iter(&app.world).collect();
+        for entity in hostiles {
+            app.world.despawn(entity);
+        }
+    }
+
+    #[test]
+    fn waves_spawn_their_count_at_their_interval() {
+        let mut app = wave_app();
+        let mut log = WaveLog::default();
+
+        log.run_seconds(&mut app, 0.25);
+        assert_eq!(log.started, vec![1]);
+        assert_eq!(count::<Asteroid>(&mut app), 0);
+        for expected in 1..=3 {
+            log.run_seconds(&mut app, 0.5);
+            assert_eq!(count::<Asteroid>(&mut app), expected);
+        }
+        // the wave is done spawning and waits to be cleared
+        log.run_seconds(&mut app, 2.0);
+        assert_eq!(count::<Asteroid>(&mut app), 3);
+        assert!(log.cleared.is_empty());
+
+        destroy_hostiles(&mut app);
+        log.run_seconds(&mut app, 0.1);
+        assert_eq!(log.cleared, vec![1]);
+
+        // nothing spawns during the gap
+        log.run_seconds(&mut app, 0.75);
+        assert_eq!(log.started, vec![1]);
+        assert_eq!(count::<Asteroid>(&mut app), 0);
+
+        log.run_seconds(&mut app, 1.0);
+        assert_eq!(log.started, vec![1, 2]);
+        assert_eq!(count::<Asteroid>(&mut app), 2);
+        assert_eq!(count::<Ufo>(&mut app), 1);
+    }
+
+    #[test]
+    fn the_last_wave_repeats() {
+        let mut app = wave_app();
+        let mut log = WaveLog::default();
+        for _ in 0..3 {
+            log.run_seconds(&mut app, 2.0);
+            destroy_hostiles(&mut app);
+            log.run_seconds(&mut app, 1.5);
+        }
+        log.run_seconds(&mut app, 1.0);
+        assert_eq!(log.started, vec![1, 2, 3, 4]);
+        assert_eq!(log.cleared, vec![1, 2, 3]);
+        assert_eq!(count::<Asteroid>(&mut app), 2);
+        assert_eq!(count::<Ufo>(&mut app), 1);
+    }
+
+    #[test]
+    fn a_wave_is_not_cleared_while_its_last_asteroid_splits() {
+        let mut app = wave_app();
+        let mut log = WaveLog::default();
+        log.run_seconds(&mut app, 2.0);
+        assert_eq!(count::<Asteroid>(&mut app), 3);
+
+        for mut health in app.world.query::<&mut Health>().iter_mut(&mut app.world) {
+            health.current = 0.0;
+        }
+        log.run_seconds(&mut app, 0.5);
+        assert!(count::<Asteroid>(&mut app) >= 6);
+        assert!(log.cleared.is_empty());
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::time::Duration;
+use bevy::prelude::*;
+use crate::collision_detection::{Collider, CollisionDamage, CollisionLayers, ContinuousCollision};
+use crate::despawn::Lifetime;
+use crate::health::Health;
+use crate::model_pool::{Model, ModelPoolAppExt, Models};
+use crate::movement::{Acceleration, MovingObjectBundle, Velocity};
+use crate::particles::{ParticleEffect, ParticleEmitter, TRAIL_RATE};
+use crate::schedule::InGameSet;
+use crate::waves::Hostile;
+
+const PROJECTILE_FORWARD_SPAWN_SCALAR: f32 = 7.5;
+const PROJECTILE_HEALTH: f32 = 0.1;
+const MAX_HEAT: f32 = 1.0;
+const UPGRADE_DAMAGE_MULTIPLIER: f32 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponKind {
+    Missile,
+    SpreadShot,
+    RapidLaser,
+    HomingMissile,
+    /// Slow single shots fired by UFOs.
+    UfoBlaster,
+}
+
+/// What limits firing besides the fire rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeaponBudget {
+    Unlimited,
+    Ammo { current: u32, max: u32 },
+    /// Every shot adds heat, the weapon can't fire past `MAX_HEAT` and cools down over time.
+    Heat { current: f32, per_shot: f32, cooling_per_second: f32 },
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    /// Shots per second.
+    pub fire_rate: f32,
+    pub projectiles: u32,
+    /// Angle in radians between neighbouring projectiles of one shot.
+    pub spread: f32,
+    pub projectile_speed: f32,
+    pub projectile_radius: f32,
+    pub projectile_scale: f32,
+    pub damage: f32,
+    /// Seconds a projectile lives before it's despawned.
+    pub lifetime: f32,
+    /// Radians per second homing projectiles turn towards their target, `None` for straight shots.
+    pub homing_turn_rate: Option<f32>,
+    pub budget: WeaponBudget,
+    cooldown: Timer,
+    /// Part of the tick the cooldown ran out in that came after it ran out. Counted towards the next cooldown,
+    /// so the fire rate doesn't depend on the tick rate.
+    overshoot: Duration,
+}
+
+impl Weapon {
+    pub fn new(kind: WeaponKind) -> Self {
+        let weapon = Self {
+            kind,
+            fire_rate: 6.0,
+            projectiles: 1,
+            spread: 0.0,
+            projectile_speed: 35.0,
+            projectile_radius: 0.5,
+            projectile_scale: 1.0,
+            damage: 12.0,
+            lifetime: 3.0,
+            homing_turn_rate: None,
+            budget: WeaponBudget::Unlimited,
+            cooldown: Timer::default(),
+            overshoot: Duration::ZERO,
+        };
+        match kind {
+            WeaponKind::Missile => weapon,
+            WeaponKind::SpreadShot => Self {
+                fire_rate: 3.0,
+                projectiles: 5,
+                spread: 0.15,
+                projectile_speed: 30.0,
+                damage: 8.0,
+                lifetime: 1.5,
+                budget: WeaponBudget::Ammo { current: 40, max: 40 },
+                ..weapon
+            },
+            WeaponKind::RapidLaser => Self {
+                fire_rate: 20.0,
+                projectile_speed: 60.0,
+                projectile_radius: 0.3,
+                projectile_scale: 0.5,
+                damage: 3.0,
+                lifetime: 1.0,
+                budget: WeaponBudget::Heat { current: 0.0, per_shot: 0.05, cooling_per_second: 0.4 },
+                ..weapon
+            },
+            WeaponKind::HomingMissile => Self {
+                fire_rate: 2.0,
+                projectile_speed: 25.0,
+                damage: 20.0,
+                lifetime: 5.0,
+                homing_turn_rate: Some(3.0),
+                budget: WeaponBudget::Ammo { current: 20, max: 20 },
+                ..weapon
+            },
+            WeaponKind::UfoBlaster => Self {
+                fire_rate: 0.8,
+                projectile_speed: 25.0,
+                projectile_scale: 0.7,
+                damage: 10.0,
+                lifetime: 2.5,
+                ..weapon
+            },
+        }
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        if !self.cooldown.finished() {
+            self.overshoot = delta.saturating_sub(self.cooldown.remaining());
+        }
+        self.cooldown.tick(delta);
+        if let WeaponBudget::Heat { current, cooling_per_second, .. } = &mut self.budget {
+            *current = (*current - *cooling_per_second * delta.as_secs_f32()).max(0.0);
+        }
+    }
+
+    /// Makes every shot hit harder and refills ammo.
+    pub fn upgrade(&mut self) {
+        self.damage *= UPGRADE_DAMAGE_MULTIPLIER;
+        if let WeaponBudget::Ammo { current, max } = &mut self.budget {
+            *current = *max;
+        }
+    }
+
+    /// Starts the cooldown and spends the budget of one shot, if the weapon is ready to fire.
+    pub fn try_fire(&mut self) -> bool {
+        if !self.cooldown.finished() {
+            return false;
+        }
+        match &mut self.budget {
+            WeaponBudget::Unlimited => (),
+            WeaponBudget::Ammo { current, .. } => {
+                if *current == 0 {
+                    return false;
+                }
+                *current -= 1;
+            }
+            WeaponBudget::Heat { current, per_shot, .. } => {
+                if *current + *per_shot > MAX_HEAT {
+                    return false;
+                }
+                *current += *per_shot;
+            }
+        }
+        self.cooldown.set_duration(Duration::from_secs_f32(1.0 / self.fire_rate));
+        self.cooldown.reset();
+        self.cooldown.tick(mem::take(&mut self.overshoot));
+        true
+    }
+}
+
+/// Weapons that aren't equipped, in the order they're cycled through.
+#[derive(Component, Debug, Default)]
+pub struct WeaponRack {
+    pub stowed: VecDeque<Weapon>,
+    /// Whether cycling was requested on the previous tick, so holding the key cycles only once.
+    pub cycle_held: bool,
+}
+
+impl WeaponRack {
+    pub fn new(kinds: impl IntoIterator<Item = WeaponKind>) -> Self {
+        Self { stowed: kinds.into_iter().map(Weapon::new).collect(), cycle_held: false }
+    }
+
+    /// Stows the equipped weapon at the back of the rack and equips the front one.
+    pub fn cycle(&mut self, equipped: &mut Weapon) {
+        if let Some(next) = self.stowed.pop_front() {
+            let previous = mem::replace(equipped, next);
+            self.stowed.push_back(previous);
+        }
+    }
+}
+
+/// Turns the projectile's velocity towards the closest hostile it can collide with.
+#[derive(Component, Debug)]
+pub struct Homing {
+    pub turn_rate: f32,
+}
+
+pub struct WeaponsPlugin;
+
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .reset_when_pooled::<Homing>()
+            .add_systems(FixedUpdate, steer_homing_projectiles.in_set(InGameSet::EntityUpdates))
+        ;
+    }
+}
+
+/// Spawns one shot of `weapon` in front of `origin`, fanned out by the weapon's spread.
+pub fn spawn_projectiles(commands: &mut Commands,
+                         models: &mut Models,
+                         weapon: &Weapon,
+                         origin: &Transform,
+                         layers: CollisionLayers,
+                         marker: impl Bundle + Clone) {
+    let forward = -origin.forward();
+    let center = (weapon.projectiles as f32 - 1.0) / 2.0;
+    for i in 0..weapon.projectiles {
+        let direction = Quat::from_rotation_y((i as f32 - center) * weapon.spread) * forward;
+        let projectile = models.spawn(commands, Model::Missile, (
+            MovingObjectBundle {
+                spatial: SpatialBundle::from_transform(
+                    Transform::from_translation(origin.translation + direction * PROJECTILE_FORWARD_SPAWN_SCALAR)
+                        .with_scale(Vec3::splat(weapon.projectile_scale)),
+                ),
+                velocity: Velocity::new(direction * weapon.projectile_speed),
+                acceleration: Acceleration::new(Vec3::ZERO),
+                collider: Collider::new(weapon.projectile_radius),
+            },
+            marker.clone(),
+            Health::new(PROJECTILE_HEALTH),
+            CollisionDamage::new(weapon.damage),
+            layers,
+            ContinuousCollision::default(),
+            Lifetime::new(weapon.lifetime),
+            ParticleEmitter::new(ParticleEffect::Trail, TRAIL_RATE),
+        ));
+        if let Some(turn_rate) = weapon.homing_turn_rate {
+            commands.entity(projectile).insert(Homing { turn_rate });
+        }
+    }
+}
+
+fn steer_homing_projectiles(mut query: Query<(&Transform, &mut Velocity, &Homing, &CollisionLayers)>,
+                            target_query: Query<(&Transform, &CollisionLayers), With<Hostile>>,
+                            time: Res<Time>) {
+    for (transform, mut velocity, homing, layers) in query.iter_mut() {
+        let closest_target = target_query
+            .iter()
+            .filter(|(_, target_layers)| layers.interacts_with(target_layers))
+            .map(|(target_transform, _)| target_transform.translation - transform.translation)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        let Some(to_target) = closest_target else {
+            continue;
+        };
+
+        let speed = velocity.value.length();
+        let current = velocity.value.normalize_or_zero();
+        let desired = to_target.normalize_or_zero();
+        let angle = current.angle_between(desired);
+        if angle <= f32::EPSILON || angle.is_nan() {
+            continue;
+        }
+        let max_turn = homing.turn_rate * time.delta_seconds();
+        let axis = current.cross(desired).try_normalize().unwrap_or(Vec3::Y);
+        velocity.value = Quat::from_axis_angle(axis, angle.min(max_turn)) * current * speed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enemies::{UFO_LAYERS, UFO_PROJECTILE_LAYERS};
+    use crate::spaceship::{Spaceship, SpaceshipPlugin, SPACESHIP_MISSILE_LAYERS};
+    use crate::testing::{headless_app, run_ticks, start_game};
+    use super::*;
+
+    /// Holds the trigger, or not, for `seconds` ticking at `tick_rate_hz`, returns how many shots were fired.
+    fn hold_fire(weapon: &mut Weapon, seconds: f32, tick_rate_hz: f32, held: bool) -> usize {
+        let delta = Duration::from_secs_f32(1.0 / tick_rate_hz);
+        (0..(seconds * tick_rate_hz).round() as usize)
+            .filter(|_| {
+                weapon.tick(delta);
+                held && weapon.try_fire()
+            })
+            .count()
+    }
+
+    #[test]
+    fn the_fire_rate_doesnt_depend_on_the_tick_rate() {
+        for tick_rate_hz in [50.0, 60.0, 144.0] {
+            let mut weapon = Weapon::new(WeaponKind::Missile);
+            // the first shot right away, then one every sixth of a second
+            assert_eq!(hold_fire(&mut weapon, 9.95, tick_rate_hz, true), 60, "{} Hz", tick_rate_hz);
+        }
+    }
+
+    #[test]
+    fn ammo_runs_out_and_upgrades_refill_it() {
+        let mut weapon = Weapon::new(WeaponKind::SpreadShot);
+        assert_eq!(hold_fire(&mut weapon, 30.0, 60.0, true), 40);
+        assert_eq!(weapon.budget, WeaponBudget::Ammo { current: 0, max: 40 });
+
+        weapon.upgrade();
+        assert_eq!(weapon.budget, WeaponBudget::Ammo { current: 40, max: 40 });
+        assert_eq!(hold_fire(&mut weapon, 0.9, 60.0, true), 3);
+        assert_eq!(weapon.budget, WeaponBudget::Ammo { current: 37, max: 40 });
+    }
+
+    fn heat(weapon: &Weapon) -> f32 {
+        let WeaponBudget::Heat { current, .. } = weapon.budget else {
+            panic!("{:?} has no heat", weapon.kind);
+        };
+        current
+    }
+
+    #[test]
+    fn overheating_slows_firing_down_to_the_cooling_rate() {
+        let mut weapon = Weapon::new(WeaponKind::RapidLaser);
+        // 20 shots a second add 1 heat a second while 0.4 cools off, so it overheats after 1 / 0.6 seconds
+        let until_overheated = hold_fire(&mut weapon, 1.0 / 0.6 - 0.1, 60.0, true);
+        assert!((30..=32).contains(&until_overheated), "{}", until_overheated);
+        assert!(heat(&weapon) > MAX_HEAT - 0.1, "{}", heat(&weapon));
+
+        // from then on only as fast as 0.4 heat a second allows, 8 shots a second
+        let overheated = hold_fire(&mut weapon, 5.0, 60.0, true);
+        assert!((39..=41).contains(&overheated), "{}", overheated);
+        assert!(heat(&weapon) <= MAX_HEAT);
+
+        hold_fire(&mut weapon, MAX_HEAT / 0.4, 60.0, false);
+        assert_eq!(heat(&weapon), 0.0);
+        assert_eq!(hold_fire(&mut weapon, 0.5, 60.0, true), 10);
+    }
+
+    #[test]
+    fn cycling_stows_the_equipped_weapon_at_the_back() {
+        let mut weapon = Weapon::new(WeaponKind::Missile);
+        let mut rack = WeaponRack::new([WeaponKind::SpreadShot, WeaponKind::HomingMissile]);
+        let mut equipped = vec![];
+        for _ in 0..4 {
+            rack.cycle(&mut weapon);
+            equipped.push(weapon.kind);
+        }
+        assert_eq!(equipped, [WeaponKind::SpreadShot, WeaponKind::HomingMissile, WeaponKind::Missile, WeaponKind::SpreadShot]);
+
+        let mut empty = WeaponRack::default();
+        empty.cycle(&mut weapon);
+        assert_eq!(weapon.kind, WeaponKind::SpreadShot);
+    }
+
+    #[test]
+    fn holding_the_cycle_key_cycles_once() {
+        let mut app = headless_app();
+        app.add_plugins(SpaceshipPlugin);
+        start_game(&mut app);
+        let equipped = |app: &mut App| app.world.query_filtered::<&Weapon, With<Spaceship>>().single(&app.world).kind;
+        assert_eq!(equipped(&mut app), WeaponKind::Missile);
+
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::Q);
+        run_ticks(&mut app, 10);
+        assert_eq!(equipped(&mut app), WeaponKind::SpreadShot);
+        app.world.resource_mut::<Input<KeyCode>>().release(KeyCode::Q);
+        run_ticks(&mut app, 1);
+        assert_eq!(equipped(&mut app), WeaponKind::SpreadShot);
+
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::Q);
+        run_ticks(&mut app, 1);
+        assert_eq!(equipped(&mut app), WeaponKind::RapidLaser);
+    }
+
+    #[test]
+    fn homing_projectiles_chase_hostiles_only() {
+        let mut app = headless_app();
+        app.add_plugins(WeaponsPlugin);
+        start_game(&mut app);
+        let at = |x, z| TransformBundle::from_transform(Transform::from_xyz(x, 0.0, z));
+        let missile = app.world.spawn((at(0.0, 0.0), Velocity::new(Vec3::X * 20.0), Homing { turn_rate: 3.0 }, SPACESHIP_MISSILE_LAYERS)).id();
+        app.world.spawn((at(10.0, 10.0), Health::new(30.0), Hostile, UFO_LAYERS));
+        // a UFO's shot can be hit too, but it's closer and not worth chasing
+        app.world.spawn((at(5.0, -3.0), Health::new(1.0), UFO_PROJECTILE_LAYERS));
+        run_ticks(&mut app, 10);
+
+        let velocity = app.world.get::<Velocity>(missile).unwrap().value;
+        assert!(velocity.z > 0.0, "{:?}", velocity);
+        assert!((velocity.length() - 20.0).abs() < 0.01);
+    }
+}